@@ -0,0 +1,832 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use clap::ValueEnum;
+use toml_edit::{DocumentMut, value};
+use crate::yaml;
+
+/// Terminals whose font settings alafont can update
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum App {
+    Alacritty,
+    Kitty,
+    Foot,
+    Wezterm,
+    Ghostty,
+    /// alacritty with a legacy alacritty.yml, used when there's no alacritty.toml
+    #[value(skip)]
+    AlacrittyYaml,
+}
+
+impl App {
+    pub const ALL: [Self; 5] = [
+        Self::Alacritty,
+        Self::Kitty,
+        Self::Foot,
+        Self::Wezterm,
+        Self::Ghostty,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Alacritty | Self::AlacrittyYaml => "alacritty",
+            Self::Kitty => "kitty",
+            Self::Foot => "foot",
+            Self::Wezterm => "wezterm",
+            Self::Ghostty => "ghostty",
+        }
+    }
+
+    /// Where the app looks for its config, preferred location first
+    fn config_candidates(self) -> Vec<PathBuf> {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")));
+
+        let mut candidates: Vec<PathBuf> = config
+            .into_iter()
+            .map(|config| match self {
+                Self::Alacritty => config.join("alacritty/alacritty.toml"),
+                Self::Kitty => config.join("kitty/kitty.conf"),
+                Self::Foot => config.join("foot/foot.ini"),
+                Self::Wezterm => config.join("wezterm/wezterm.lua"),
+                Self::Ghostty => config.join("ghostty/config"),
+                Self::AlacrittyYaml => config.join("alacritty/alacritty.yml"),
+            })
+            .collect();
+
+        if let Some(home) = home {
+            match self {
+                Self::Wezterm => candidates.push(home.join(".wezterm.lua")),
+                Self::AlacrittyYaml => candidates.push(home.join(".alacritty.yml")),
+                _ => {}
+            }
+        }
+
+        candidates
+    }
+
+    /// The app's config file, if it has one
+    pub fn config_path(self) -> Option<PathBuf> {
+        self.config_candidates()
+            .into_iter()
+            .find(|path| path.exists())
+    }
+
+    /// The app and config file to edit, alacritty falling back to a legacy alacritty.yml
+    pub fn locate(self) -> Option<(Self, PathBuf)> {
+        self.config_path().map(|path| (self, path)).or_else(|| {
+            (self == Self::Alacritty)
+                .then(|| Self::AlacrittyYaml.locate())
+                .flatten()
+        })
+    }
+
+    /// The app for an explicitly given config file, alacritty's being YAML by extension
+    pub fn for_path(self, path: &Path) -> Self {
+        let yaml = path
+            .extension()
+            .is_some_and(|ext| ext == "yml" || ext == "yaml");
+        if self == Self::Alacritty && yaml {
+            Self::AlacrittyYaml
+        } else {
+            self
+        }
+    }
+
+    /// The font and window settings in the app's config file contents
+    pub fn current(self, content: &str) -> Current {
+        let number = |value: Option<&str>| value.and_then(|v| unquote(v).parse::<f64>().ok());
+        let string = |value: Option<&str>| value.map(|v| unquote(v).to_string());
+
+        let mut current = Current {
+            size: self.font_size(content),
+            ..Current::default()
+        };
+
+        match self {
+            Self::Alacritty => {
+                let Ok(doc) = content.parse::<DocumentMut>() else {
+                    return current;
+                };
+                let get = |path: &[&str]| {
+                    path.iter()
+                        .try_fold(doc.as_item(), |item, key| item.get(key))
+                };
+                let text = |path: &[&str]| get(path)?.as_str().map(str::to_string);
+                let float = |path: &[&str]| {
+                    let item = get(path)?;
+                    item.as_float()
+                        .or_else(|| item.as_integer().map(|i| i as f64))
+                };
+
+                current.font = text(&["font", "normal", "family"]);
+                current.bold = text(&["font", "bold", "family"]);
+                current.italic = text(&["font", "italic", "family"]);
+                current.bold_italic = text(&["font", "bold_italic", "family"]);
+                current.opacity = float(&["window", "opacity"]);
+                current.padding = padding(
+                    float(&["window", "padding", "x"]),
+                    float(&["window", "padding", "y"]),
+                );
+            }
+            Self::AlacrittyYaml => {
+                let get = |path: &[&str]| yaml::get(content, path);
+                current.font = string(get(&["font", "normal", "family"]));
+                current.bold = string(get(&["font", "bold", "family"]));
+                current.italic = string(get(&["font", "italic", "family"]));
+                current.bold_italic = string(get(&["font", "bold_italic", "family"]));
+                current.opacity = number(get(&["window", "opacity"]));
+                current.padding = padding(
+                    number(get(&["window", "padding", "x"])),
+                    number(get(&["window", "padding", "y"])),
+                );
+            }
+            Self::Kitty => {
+                // font_family is either the family or `family="X" style="Y"`
+                current.font = line_value(content, "font_family").map(|family| {
+                    family
+                        .strip_prefix("family=")
+                        .and_then(|rest| rest.strip_prefix('"'))
+                        .and_then(|rest| rest.split('"').next())
+                        .unwrap_or(family)
+                        .to_string()
+                });
+                current.bold = string(line_value(content, "bold_font"));
+                current.italic = string(line_value(content, "italic_font"));
+                current.bold_italic = string(line_value(content, "bold_italic_font"));
+                current.opacity = number(line_value(content, "background_opacity"));
+
+                // One value for all sides, or vertical then horizontal
+                let sides: Vec<f64> = line_value(content, "window_padding_width")
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .filter_map(|n| n.parse().ok())
+                    .collect();
+                current.padding = match sides[..] {
+                    [all] => padding(Some(all), Some(all)),
+                    [y, x, ..] => padding(Some(x), Some(y)),
+                    [] => None,
+                };
+            }
+            Self::Foot => {
+                let family = |key: &str| {
+                    let font = line_value(content, key)?.split(',').next()?;
+                    font.split(':')
+                        .next()
+                        .map(|family| family.trim().to_string())
+                };
+                current.font = family("font");
+                current.bold = family("font-bold");
+                current.italic = family("font-italic");
+                current.bold_italic = family("font-bold-italic");
+                current.opacity = number(line_value(content, "alpha"));
+                current.padding = line_value(content, "pad")
+                    .and_then(|pad| pad.split_whitespace().next())
+                    .and_then(|pad| pad.parse().ok());
+            }
+            Self::Ghostty => {
+                current.font = string(line_value(content, "font-family"));
+                current.bold = string(line_value(content, "font-family-bold"));
+                current.italic = string(line_value(content, "font-family-italic"));
+                current.bold_italic = string(line_value(content, "font-family-bold-italic"));
+                current.opacity = number(line_value(content, "background-opacity"));
+                current.padding = padding(
+                    number(line_value(content, "window-padding-x")),
+                    number(line_value(content, "window-padding-y")),
+                );
+            }
+            Self::Wezterm => {
+                let field = |key: &str| {
+                    line_value(content, &format!("config.{key}"))
+                        .or_else(|| line_value(content, key))
+                        .map(|value| value.trim_end_matches(',').trim())
+                };
+                // The first family of wezterm.font("X") or font_with_fallback({ "X", ... })
+                current.font = field("font")
+                    .and_then(|font| font.split('"').nth(1))
+                    .map(str::to_string);
+                current.opacity = number(field("window_background_opacity"));
+                current.padding = field("window_padding").and_then(|table| {
+                    let side = |side: &str| {
+                        table.split([',', '{', '}']).find_map(|entry| {
+                            let (key, value) = entry.split_once('=')?;
+                            (key.trim() == side).then(|| value.trim().parse().ok())?
+                        })
+                    };
+                    padding(side("left"), side("top"))
+                });
+            }
+        }
+
+        current
+    }
+
+    /// The font size currently set in the app's config file contents
+    pub fn font_size(self, content: &str) -> Option<f64> {
+        match self {
+            Self::AlacrittyYaml => yaml::get(content, &["font", "size"])?.parse().ok(),
+            Self::Alacritty => {
+                let doc = content.parse::<DocumentMut>().ok()?;
+                let size = doc.get("font")?.get("size")?;
+                size.as_float()
+                    .or_else(|| size.as_integer().map(|i| i as f64))
+            }
+            Self::Kitty => line_value(content, "font_size")?.parse().ok(),
+            Self::Ghostty => line_value(content, "font-size")?.parse().ok(),
+            Self::Foot => {
+                let font = line_value(content, "font")?.split(',').next()?;
+                font.split(':')
+                    .find_map(|attr| attr.trim().strip_prefix("size="))?
+                    .parse()
+                    .ok()
+            }
+            Self::Wezterm => line_value(content, "config.font_size")
+                .or_else(|| line_value(content, "font_size"))?
+                .trim_end_matches(',')
+                .trim()
+                .parse()
+                .ok(),
+        }
+    }
+
+    /// Set the font size, and the fonts unless they're `None`, in the app's config file contents
+    ///
+    /// Settings the app has no equivalent for are skipped with a warning.
+    pub fn set_font(
+        self,
+        content: &str,
+        font: Option<&Font>,
+        size: f64,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let skipped = |what: &str, why: &str| eprintln!("{}: skipped {what}, {why}", self.name());
+
+        Ok(match self {
+            Self::Alacritty => {
+                let mut doc = content.parse::<DocumentMut>()?;
+                ensure_table(&mut doc, "font");
+
+                doc["font"]["size"] = value(size);
+
+                if let Some(font) = font {
+                    doc["font"]["normal"]["family"] = value(&font.family);
+                    // The previous font's style may not exist in this family
+                    match &font.style {
+                        Some(style) => doc["font"]["normal"]["style"] = value(style),
+                        None => {
+                            if let Some(normal) = doc["font"]["normal"].as_table_like_mut() {
+                                normal.remove("style");
+                            }
+                        }
+                    }
+
+                    doc["font"]["bold"]["family"] = value(font.bold_or_family());
+                    doc["font"]["italic"]["family"] = value(font.italic_or_family());
+                    // Only written when asked for or already there, alacritty
+                    // falls back to the normal family otherwise
+                    if font.bold_italic.is_some() || doc["font"].get("bold_italic").is_some() {
+                        doc["font"]["bold_italic"]["family"] = value(font.bold_italic_or_family());
+                    }
+
+                    if !font.fallback.is_empty() {
+                        skipped("--fallback", "alacritty uses fontconfig's fallbacks");
+                    }
+                }
+
+                doc.to_string()
+            }
+            Self::AlacrittyYaml => {
+                let mut content = yaml::set(content, &["font", "size"], &format!("{size:?}"))?;
+
+                if let Some(font) = font {
+                    // Bold italic is only written when asked for or already there
+                    let bold_italic = font.bold_italic.is_some()
+                        || yaml::get(&content, &["font", "bold_italic", "family"]).is_some();
+                    let faces = [
+                        Some(("normal", font.family.as_str())),
+                        Some(("bold", font.bold_or_family())),
+                        Some(("italic", font.italic_or_family())),
+                        bold_italic.then(|| ("bold_italic", font.bold_italic_or_family())),
+                    ];
+                    for (face, family) in faces.into_iter().flatten() {
+                        let path = ["font", face, "family"];
+                        content = yaml::set(&content, &path, &yaml::quote(family))?;
+                    }
+
+                    let style = ["font", "normal", "style"];
+                    content = match &font.style {
+                        Some(value) => yaml::set(&content, &style, &yaml::quote(value))?,
+                        None => yaml::remove(&content, &style),
+                    };
+                    if !font.fallback.is_empty() {
+                        skipped("--fallback", "alacritty uses fontconfig's fallbacks");
+                    }
+                }
+
+                content
+            }
+            Self::Kitty => {
+                // Faces that aren't set default to "auto", derived from font_family
+                let mut content = content.to_string();
+                if let Some(font) = font {
+                    let family = match &font.style {
+                        Some(style) => format!("family=\"{}\" style=\"{style}\"", font.family),
+                        None => font.family.clone(),
+                    };
+                    content = set_line(&content, "font_family", &format!("font_family {family}"));
+
+                    for (key, face) in [
+                        ("bold_font", &font.bold),
+                        ("italic_font", &font.italic),
+                        ("bold_italic_font", &font.bold_italic),
+                    ] {
+                        // A face left over from the previous font goes back to auto
+                        match face {
+                            Some(face) => {
+                                content = set_line(&content, key, &format!("{key} {face}"));
+                            }
+                            None if line_value(&content, key).is_some() => {
+                                content = set_line(&content, key, &format!("{key} auto"));
+                            }
+                            None => {}
+                        }
+                    }
+
+                    if !font.fallback.is_empty() {
+                        skipped("--fallback", "kitty configures fallbacks with symbol_map");
+                    }
+                }
+                set_line(&content, "font_size", &format!("font_size {size}"))
+            }
+            Self::Foot => {
+                let Some(font) = font else {
+                    // Keep the fonts, including any style and fallbacks, and
+                    // only change their sizes
+                    let mut content = content.to_string();
+                    for key in FOOT_FONT_KEYS {
+                        if let Some(current) = line_value(&content, key) {
+                            let line = format!("{key}={}", foot_resize(current, size));
+                            content = set_ini_line(&content, "main", key, &line);
+                        }
+                    }
+                    if line_value(&content, "font").is_none() {
+                        return Err("No font set in foot.ini, pass one with --font".into());
+                    }
+                    return Ok(content);
+                };
+
+                // Fallbacks follow the primary font, comma separated
+                let pattern = |family: &str, style: Option<&str>| {
+                    let mut fonts = vec![match style {
+                        Some(style) => format!("{family}:style={style}:size={size}"),
+                        None => format!("{family}:size={size}"),
+                    }];
+                    fonts.extend(font.fallback.iter().map(|f| format!("{f}:size={size}")));
+                    fonts.join(",")
+                };
+
+                let mut content = set_ini_line(
+                    content,
+                    "main",
+                    "font",
+                    &format!("font={}", pattern(&font.family, font.style.as_deref())),
+                );
+                for (key, face) in
+                    FOOT_FONT_KEYS[1..]
+                        .iter()
+                        .zip([&font.bold, &font.italic, &font.bold_italic])
+                {
+                    // Without a face of its own the style is derived from font
+                    match face {
+                        Some(face) => {
+                            let line = format!("{key}={}", pattern(face, None));
+                            content = set_ini_line(&content, "main", key, &line);
+                        }
+                        None => content = set_lines(&content, key, &[]),
+                    }
+                }
+                content
+            }
+            Self::Ghostty => {
+                let mut content = content.to_string();
+                if let Some(font) = font {
+                    // Repeated font-family settings are the fallbacks
+                    if font.fallback.is_empty() {
+                        content = set_line(
+                            &content,
+                            "font-family",
+                            &format!("font-family = {}", font.family),
+                        );
+                    } else {
+                        let lines: Vec<String> = std::iter::once(&font.family)
+                            .chain(&font.fallback)
+                            .map(|family| format!("font-family = {family}"))
+                            .collect();
+                        content = set_lines(&content, "font-family", &lines);
+                    }
+
+                    for (key, setting) in [
+                        ("font-style", &font.style),
+                        ("font-family-bold", &font.bold),
+                        ("font-family-italic", &font.italic),
+                        ("font-family-bold-italic", &font.bold_italic),
+                    ] {
+                        // Settings made for the previous font are dropped
+                        match setting {
+                            Some(setting) => {
+                                content = set_line(&content, key, &format!("{key} = {setting}"));
+                            }
+                            None => content = set_lines(&content, key, &[]),
+                        }
+                    }
+                }
+                set_line(&content, "font-size", &format!("font-size = {size}"))
+            }
+            Self::Wezterm => {
+                let mut content = content.to_string();
+                if let Some(font) = font {
+                    let lua_font = if font.fallback.is_empty() {
+                        format!("wezterm.font({:?})", font.family)
+                    } else {
+                        let families: Vec<String> = std::iter::once(&font.family)
+                            .chain(&font.fallback)
+                            .map(|family| format!("{family:?}"))
+                            .collect();
+                        format!("wezterm.font_with_fallback({{ {} }})", families.join(", "))
+                    };
+                    content = set_lua_field(&content, "font", &lua_font)?;
+
+                    if font.style.is_some() {
+                        skipped(
+                            "--style",
+                            "wezterm.font takes weights rather than style names",
+                        );
+                    }
+                    if font.bold.is_some() || font.italic.is_some() || font.bold_italic.is_some() {
+                        skipped("per-style fonts", "wezterm configures them with font_rules");
+                    }
+                }
+                set_lua_field(&content, "font_size", &size.to_string())?
+            }
+        })
+    }
+
+    /// Set the window opacity and padding that are `Some` in the app's config file contents
+    pub fn set_window(
+        self,
+        content: &str,
+        window: &Window,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut content = content.to_string();
+
+        match self {
+            Self::Alacritty => {
+                let mut doc = content.parse::<DocumentMut>()?;
+                ensure_table(&mut doc, "window");
+
+                if let Some(opacity) = window.opacity {
+                    doc["window"]["opacity"] = value(opacity);
+                }
+                if let Some(padding) = window.padding {
+                    doc["window"]["padding"]["x"] = value(i64::from(padding.x));
+                    doc["window"]["padding"]["y"] = value(i64::from(padding.y));
+                }
+                content = doc.to_string();
+            }
+            Self::AlacrittyYaml => {
+                if let Some(opacity) = window.opacity {
+                    content = yaml::set(&content, &["window", "opacity"], &format!("{opacity:?}"))?;
+                }
+                if let Some(padding) = window.padding {
+                    let x = padding.x.to_string();
+                    let y = padding.y.to_string();
+                    content = yaml::set(&content, &["window", "padding", "x"], &x)?;
+                    content = yaml::set(&content, &["window", "padding", "y"], &y)?;
+                }
+            }
+            Self::Kitty => {
+                if let Some(opacity) = window.opacity {
+                    let line = format!("background_opacity {opacity}");
+                    content = set_line(&content, "background_opacity", &line);
+                }
+                if let Some(padding) = window.padding {
+                    // Two values are vertical and horizontal, as in CSS
+                    let line = format!("window_padding_width {} {}", padding.y, padding.x);
+                    content = set_line(&content, "window_padding_width", &line);
+                }
+            }
+            Self::Foot => {
+                if let Some(opacity) = window.opacity {
+                    content =
+                        set_ini_line(&content, "colors", "alpha", &format!("alpha={opacity}"));
+                }
+                if let Some(padding) = window.padding {
+                    let line = format!("pad={}x{}", padding.x, padding.y);
+                    content = set_ini_line(&content, "main", "pad", &line);
+                }
+            }
+            Self::Ghostty => {
+                if let Some(opacity) = window.opacity {
+                    let line = format!("background-opacity = {opacity}");
+                    content = set_line(&content, "background-opacity", &line);
+                }
+                if let Some(padding) = window.padding {
+                    let x = format!("window-padding-x = {}", padding.x);
+                    let y = format!("window-padding-y = {}", padding.y);
+                    content = set_line(&content, "window-padding-x", &x);
+                    content = set_line(&content, "window-padding-y", &y);
+                }
+            }
+            Self::Wezterm => {
+                if let Some(opacity) = window.opacity {
+                    content =
+                        set_lua_field(&content, "window_background_opacity", &opacity.to_string())?;
+                }
+                if let Some(Padding { x, y }) = window.padding {
+                    let padding = format!("{{ left = {x}, right = {x}, top = {y}, bottom = {y} }}");
+                    content = set_lua_field(&content, "window_padding", &padding)?;
+                }
+            }
+        }
+
+        Ok(content)
+    }
+}
+
+/// Font and window settings found in a config, `None` where it has none
+#[derive(Debug, Default)]
+pub struct Current {
+    pub font: Option<String>,
+    pub bold: Option<String>,
+    pub italic: Option<String>,
+    pub bold_italic: Option<String>,
+    pub size: Option<f64>,
+    pub opacity: Option<f64>,
+    pub padding: Option<Padding>,
+}
+
+fn padding(x: Option<f64>, y: Option<f64>) -> Option<Padding> {
+    Some(Padding {
+        x: x? as u32,
+        y: y? as u32,
+    })
+}
+
+/// A config value without its surrounding quotes
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|&quote| value.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(value)
+}
+
+/// Window settings to write, `None` leaves one as it is
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Window {
+    pub opacity: Option<f64>,
+    pub padding: Option<Padding>,
+}
+
+impl Window {
+    pub fn is_empty(&self) -> bool {
+        self.opacity.is_none() && self.padding.is_none()
+    }
+}
+
+/// Background opacity, which terminals take from 0 to 1
+pub fn check_opacity(opacity: f64) -> Result<f64, String> {
+    if !(0.0..=1.0).contains(&opacity) {
+        return Err(format!("Opacity {opacity} isn't between 0 and 1"));
+    }
+    Ok(opacity)
+}
+
+/// Horizontal and vertical padding in pixels, written as `XxY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Padding {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl std::fmt::Display for Padding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.x, self.y)
+    }
+}
+
+impl FromStr for Padding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (x, y) = value
+            .split_once('x')
+            .ok_or_else(|| format!("Padding '{value}' isn't in the form XxY, e.g. 12x8"))?;
+        let number = |n: &str| {
+            n.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("Padding '{value}' isn't in the form XxY, e.g. 12x8"))
+        };
+        Ok(Self {
+            x: number(x)?,
+            y: number(y)?,
+        })
+    }
+}
+
+/// Font families to write, the faces default to the app's own choice
+#[derive(Debug, Default, Clone)]
+pub struct Font {
+    pub family: String,
+    /// Style of the normal face, e.g. "Medium"
+    pub style: Option<String>,
+    pub bold: Option<String>,
+    pub italic: Option<String>,
+    pub bold_italic: Option<String>,
+    /// Families for glyphs the main font doesn't have, in order
+    pub fallback: Vec<String>,
+}
+
+impl Font {
+    pub fn new(family: &str) -> Self {
+        Self {
+            family: family.to_string(),
+            ..Self::default()
+        }
+    }
+
+    fn bold_or_family(&self) -> &str {
+        self.bold.as_deref().unwrap_or(&self.family)
+    }
+
+    fn italic_or_family(&self) -> &str {
+        self.italic.as_deref().unwrap_or(&self.family)
+    }
+
+    fn bold_italic_or_family(&self) -> &str {
+        self.bold_italic.as_deref().unwrap_or(&self.family)
+    }
+}
+
+/// foot's font settings, which all carry their own size
+const FOOT_FONT_KEYS: [&str; 4] = ["font", "font-bold", "font-italic", "font-bold-italic"];
+
+/// Set the size of every font in a foot font list like `A:size=9,B:size=9`
+fn foot_resize(fonts: &str, size: f64) -> String {
+    fonts
+        .split(',')
+        .map(|font| {
+            let mut attrs: Vec<String> = font
+                .trim()
+                .split(':')
+                .filter(|attr| !attr.starts_with("size=") && !attr.starts_with("pixelsize="))
+                .map(str::to_string)
+                .collect();
+            attrs.push(format!("size={size}"));
+            attrs.join(":")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Add a `[key]` table at the end of the document if it doesn't have one,
+/// rather than the inline table indexing would create
+fn ensure_table(doc: &mut DocumentMut, key: &str) {
+    if !doc.contains_key(key) {
+        doc[key] = toml_edit::table();
+    }
+}
+
+/// Replace the first line setting `key` (`key value` or `key = value`), or append one
+fn set_line(content: &str, key: &str, line: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    match lines.iter().position(|l| sets_key(l, key)) {
+        Some(i) => lines[i] = line.to_string(),
+        None => lines.push(line.to_string()),
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Replace every line setting `key` with `new`, at the first one's place, or append them
+fn set_lines(content: &str, key: &str, new: &[String]) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    let first = lines.iter().position(|l| sets_key(l, key));
+    lines.retain(|l| !sets_key(l, key));
+
+    let at = first.unwrap_or(lines.len());
+    lines.splice(at..at, new.iter().cloned());
+
+    lines.join("\n") + "\n"
+}
+
+/// Replace `key=` in an ini section, or add it at the end of the section
+///
+/// Keys before the first section header belong to `[main]`, as in foot.
+fn set_ini_line(content: &str, section: &str, key: &str, line: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let header = format!("[{section}]");
+
+    let mut in_section = section == "main";
+    let mut found = None;
+    // Where to add the key, after the section's last line
+    let mut end = in_section.then_some(0);
+
+    for (i, l) in lines.iter().enumerate() {
+        let trimmed = l.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            in_section = trimmed == header;
+            if in_section {
+                end = Some(i + 1);
+            }
+        } else if in_section && sets_key(l, key) {
+            found = Some(i);
+            break;
+        } else if in_section && !trimmed.is_empty() {
+            end = Some(i + 1);
+        }
+    }
+
+    match (found, end) {
+        (Some(i), _) => lines[i] = line.to_string(),
+        (None, Some(end)) => lines.insert(end, line.to_string()),
+        (None, None) => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.push(line.to_string());
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Replace the value of a `key = ...` field in a wezterm Lua config
+///
+/// Handles both `config.key = ...` assignments and `key = ...,` fields of a
+/// returned table. Missing fields are added before `return config`.
+fn set_lua_field(
+    content: &str,
+    key: &str,
+    lua_value: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    let position = lines.iter().position(|l| {
+        let l = l.trim_start();
+        let l = l.strip_prefix("config.").unwrap_or(l);
+        l.strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+
+    if let Some(i) = position
+        && let Some((lhs, rhs)) = lines[i].split_once('=')
+    {
+        // Values spanning several lines would be left half replaced
+        let opened = rhs.matches(['{', '(']).count();
+        let closed = rhs.matches(['}', ')']).count();
+        if opened != closed {
+            return Err(format!(
+                "'{key}' in wezterm.lua spans several lines, put it on one line to let alafont change it"
+            )
+            .into());
+        }
+
+        // Keep the trailing comma of table fields
+        let comma = if rhs.trim_end().ends_with(',') {
+            ","
+        } else {
+            ""
+        };
+        lines[i] = format!("{} = {lua_value}{comma}", lhs.trim_end());
+    } else if let Some(i) = lines.iter().position(|l| l.trim() == "return config") {
+        lines.insert(i, format!("config.{key} = {lua_value}"));
+    } else {
+        return Err(format!(
+            "No '{key}' setting found in wezterm.lua and no `return config` to add it before"
+        )
+        .into());
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Value of the first line setting `key`, with either `key value` or `key = value`
+fn line_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    content
+        .lines()
+        .find(|l| sets_key(l, key))?
+        .trim_start()
+        .strip_prefix(key)
+        .map(|rest| rest.trim().trim_start_matches('=').trim())
+}
+
+/// Whether an uncommented line sets `key`, with either `key value` or `key = value`
+fn sets_key(line: &str, key: &str) -> bool {
+    line.trim_start()
+        .strip_prefix(key)
+        .is_some_and(|rest| rest.starts_with([' ', '\t', '=']))
+}
@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config;
+
+/// Number of backups kept, older ones are removed
+const KEEP: usize = 20;
+
+/// Backups live in `~/.local/share/alafont/backups/<millis>/`, one directory
+/// per run holding the previous contents of every config it changed
+fn backup_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(config::data_dir()?.join("backups"))
+}
+
+/// Save the contents configs had before a run, then drop the oldest backups
+pub fn save(files: &[(&Path, &str)]) -> Result<(), Box<dyn std::error::Error>> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let dir = backup_dir()?.join(format!("{millis:016}"));
+    fs::create_dir_all(&dir)?;
+
+    // The config paths, one per line, in the order of the numbered files
+    let mut paths = String::new();
+    for (i, (path, content)) in files.iter().enumerate() {
+        fs::write(dir.join(i.to_string()), content)?;
+        paths.push_str(&path.to_string_lossy());
+        paths.push('\n');
+    }
+    fs::write(dir.join("paths"), paths)?;
+
+    let backups = list()?;
+    for old in backups.iter().take(backups.len().saturating_sub(KEEP)) {
+        fs::remove_dir_all(old)?;
+    }
+
+    Ok(())
+}
+
+/// Backup directories, oldest first
+fn list() -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let dir = backup_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join("paths").exists())
+        .collect();
+    backups.sort();
+    Ok(backups)
+}
+
+/// The configs in the newest backup with the contents `undo` would put back
+pub fn latest() -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let backup = list()?.pop().ok_or("No backups to undo")?;
+    read(&backup)
+}
+
+fn read(backup: &Path) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let paths = fs::read_to_string(backup.join("paths"))?;
+    paths
+        .lines()
+        .enumerate()
+        .map(|(i, path)| {
+            let content = fs::read_to_string(backup.join(i.to_string()))?;
+            Ok((PathBuf::from(path), content))
+        })
+        .collect()
+}
+
+/// Put back the configs from the newest backup and remove it
+///
+/// Returns the restored config paths.
+pub fn undo() -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let backup = list()?.pop().ok_or("No backups to undo")?;
+
+    let files = read(&backup)?;
+    for (path, content) in &files {
+        fs::write(path, content)
+            .map_err(|e| format!("Failed to restore {}: {e}", path.display()))?;
+    }
+
+    fs::remove_dir_all(&backup)?;
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
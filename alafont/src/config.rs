@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use toml_edit::{DocumentMut, Item};
+use crate::apps::{self, Padding};
+use crate::integrations::{Integration, Tool};
+
+/// alafont's own settings live in `~/.config/alafont`
+pub fn dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|base| base.join("alafont"))
+}
+
+/// alafont's state (backups, the favourite in use) lives in `~/.local/share/alafont`
+pub fn data_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .ok_or("Neither XDG_DATA_HOME nor HOME is set")?;
+    Ok(data.join("alafont"))
+}
+
+/// Settings from `~/.config/alafont/config.toml`
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Named font sizes from the `[presets]` table
+    pub presets: BTreeMap<String, f64>,
+    /// Named sets of settings from the `[profiles.<name>]` tables
+    pub profiles: BTreeMap<String, Profile>,
+    /// Fonts `alafont cycle` goes through, in order
+    pub favourites: Vec<String>,
+    /// Other tools' configs whose font follows the terminal's, from `[integrations]`
+    pub integrations: Vec<Integration>,
+}
+
+/// Settings applied together by `alafont profile`, unset ones are left as they are
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub font: Option<String>,
+    pub size: Option<f64>,
+    pub opacity: Option<f64>,
+    pub padding: Option<Padding>,
+}
+
+impl Config {
+    /// Load the config, or the defaults when there's no config file
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(path) = dir().map(|dir| dir.join("config.toml")) else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)?;
+        let doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+
+        let mut config = Self::default();
+
+        if let Some(presets) = doc.get("presets") {
+            let presets = presets
+                .as_table_like()
+                .ok_or_else(|| format!("[presets] in {} must be a table", path.display()))?;
+            for (name, size) in presets.iter() {
+                let size = number(size).ok_or_else(|| {
+                    format!("Preset '{name}' in {} must be a number", path.display())
+                })?;
+                config.presets.insert(name.to_string(), size);
+            }
+        }
+
+        if let Some(profiles) = doc.get("profiles") {
+            let profiles = profiles
+                .as_table_like()
+                .ok_or_else(|| format!("[profiles] in {} must be a table", path.display()))?;
+            for (name, settings) in profiles.iter() {
+                let profile = parse_profile(settings)
+                    .map_err(|e| format!("Profile '{name}' in {}: {e}", path.display()))?;
+                config.profiles.insert(name.to_string(), profile);
+            }
+        }
+
+        if let Some(favourites) = doc.get("favourites") {
+            let favourites = favourites
+                .as_array()
+                .ok_or_else(|| format!("favourites in {} must be an array", path.display()))?;
+            for font in favourites {
+                let font = font.as_str().ok_or_else(|| {
+                    format!("favourites in {} must be font names", path.display())
+                })?;
+                config.favourites.push(font.to_string());
+            }
+        }
+
+        if let Some(integrations) = doc.get("integrations") {
+            let integrations = integrations
+                .as_table_like()
+                .ok_or_else(|| format!("[integrations] in {} must be a table", path.display()))?;
+            for (name, file) in integrations.iter() {
+                let tool = Tool::from_name(name).ok_or_else(|| {
+                    format!(
+                        "Unknown integration '{name}' in {}, expected rofi, waybar or dunst",
+                        path.display()
+                    )
+                })?;
+                let file = file.as_str().ok_or_else(|| {
+                    format!("Integration '{name}' in {} must be a path", path.display())
+                })?;
+                config.integrations.push(Integration {
+                    tool,
+                    path: expand_home(file),
+                });
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// `path` with a leading `~/` expanded to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn number(item: &Item) -> Option<f64> {
+    item.as_float()
+        .or_else(|| item.as_integer().map(|i| i as f64))
+}
+
+fn parse_profile(settings: &Item) -> Result<Profile, String> {
+    let settings = settings.as_table_like().ok_or("must be a table")?;
+
+    let mut profile = Profile::default();
+    for (key, item) in settings.iter() {
+        match key {
+            "font" => {
+                profile.font = Some(item.as_str().ok_or("font must be a string")?.to_string())
+            }
+            "size" => profile.size = Some(number(item).ok_or("size must be a number")?),
+            "opacity" => {
+                let opacity = number(item).ok_or("opacity must be a number")?;
+                profile.opacity = Some(apps::check_opacity(opacity)?);
+            }
+            "padding" => {
+                let padding = item
+                    .as_str()
+                    .ok_or("padding must be a string like \"12x8\"")?;
+                profile.padding = Some(padding.parse()?);
+            }
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+    }
+
+    Ok(profile)
+}
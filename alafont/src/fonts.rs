@@ -0,0 +1,84 @@
+use std::process::Command;
+use crate::apps::Font;
+
+/// Names of the installed fonts matching a fontconfig pattern, one list per font
+///
+/// Fonts with localized names have several, the first is the usual one.
+fn fc_list(pattern: &str) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+    let output = Command::new("fc-list")
+        .args([pattern, "family"])
+        .output()
+        .map_err(|e| format!("Failed to run fc-list: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("fc-list failed: {}", output.status).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            line.split(',')
+                .map(|family| family.trim().to_string())
+                .filter(|family| !family.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .filter(|names| !names.is_empty())
+        .collect())
+}
+
+/// Families of the installed monospace fonts, sorted
+pub fn monospace() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut families: Vec<String> = fc_list(":spacing=mono")?
+        .into_iter()
+        .filter_map(|names| names.into_iter().next())
+        .collect();
+
+    families.sort();
+    families.dedup();
+    Ok(families)
+}
+
+/// Check that every family of `font` is installed
+///
+/// Unknown families are an error naming the closest installed one, or only
+/// a warning with `force`.
+pub fn check(font: &Font, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let installed: Vec<String> = fc_list(":")?.into_iter().flatten().collect();
+
+    let faces = [&font.bold, &font.italic, &font.bold_italic];
+    let families = std::iter::once(&font.family)
+        .chain(faces.into_iter().flatten())
+        .chain(&font.fallback);
+
+    for family in families {
+        if installed
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(family))
+        {
+            continue;
+        }
+
+        let mut message = format!("Font '{family}' isn't installed");
+        if let Some(suggestion) = closest(family, &installed) {
+            message.push_str(&format!(", did you mean '{suggestion}'?"));
+        }
+
+        if !force {
+            return Err(format!("{message} (--force writes it anyway)").into());
+        }
+        eprintln!("Warning: {message}");
+    }
+
+    Ok(())
+}
+
+/// The installed family most like `family`, if any is close enough
+fn closest<'a>(family: &str, installed: &'a [String]) -> Option<&'a str> {
+    let family = family.to_lowercase();
+
+    installed
+        .iter()
+        .map(|name| (name, strsim::jaro_winkler(&family, &name.to_lowercase())))
+        .filter(|(_, score)| *score >= 0.85)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(name, _)| name.as_str())
+}
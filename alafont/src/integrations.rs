@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+/// Other tools whose font follows the terminal's
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    Rofi,
+    Waybar,
+    Dunst,
+}
+
+impl Tool {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rofi" => Some(Self::Rofi),
+            "waybar" => Some(Self::Waybar),
+            "dunst" => Some(Self::Dunst),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rofi => "rofi",
+            Self::Waybar => "waybar",
+            Self::Dunst => "dunst",
+        }
+    }
+
+    /// Replace the font family in the tool's config file contents, keeping
+    /// its own font sizes
+    ///
+    /// Returns `None` when the file has no font setting to change.
+    pub fn set_font(self, content: &str, family: &str) -> Option<String> {
+        let mut changed = false;
+
+        let lines: Vec<String> = content
+            .lines()
+            .map(|line| {
+                let new = match self {
+                    Self::Rofi => rofi_line(line, family),
+                    Self::Waybar => waybar_line(line, family),
+                    Self::Dunst => dunst_line(line, family),
+                };
+                changed |= new.is_some();
+                new.unwrap_or_else(|| line.to_string())
+            })
+            .collect();
+
+        changed.then(|| lines.join("\n") + "\n")
+    }
+}
+
+/// A tool config to keep in line, from `[integrations]` in config.toml
+#[derive(Debug, Clone)]
+pub struct Integration {
+    pub tool: Tool,
+    pub path: PathBuf,
+}
+
+/// `family` with the size at the end of a `Family Size` font string, if it had one
+fn keep_size(old: &str, family: &str) -> String {
+    match old.rsplit_once(' ') {
+        Some((_, size)) if size.parse::<f64>().is_ok() => format!("{family} {size}"),
+        _ => family.to_string(),
+    }
+}
+
+/// `font: "Family 12";` in a rasi theme or config
+fn rofi_line(line: &str, family: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let rest = trimmed.strip_prefix("font:")?;
+    let old = rest.trim().trim_end_matches(';').trim().trim_matches('"');
+
+    let indent = &line[..line.len() - trimmed.len()];
+    Some(format!("{indent}font: \"{}\";", keep_size(old, family)))
+}
+
+/// `font-family: "Family", fallback;` in a stylesheet, replacing only the first family
+fn waybar_line(line: &str, family: &str) -> Option<String> {
+    let start = line.find("font-family:")? + "font-family:".len();
+    let end = line[start..]
+        .find([';', '}'])
+        .map_or(line.len(), |i| start + i);
+
+    let families = &line[start..end];
+    let rest = families.split_once(',').map(|(_, rest)| rest);
+    let value = match rest {
+        Some(rest) => format!(" \"{family}\",{rest}"),
+        None => format!(" \"{family}\""),
+    };
+
+    Some(format!("{}{value}{}", &line[..start], &line[end..]))
+}
+
+/// `font = Family 10` in dunstrc
+fn dunst_line(line: &str, family: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let rest = trimmed
+        .strip_prefix("font")?
+        .trim_start()
+        .strip_prefix('=')?;
+    let old = rest.trim().trim_matches('"');
+
+    let indent = &line[..line.len() - trimmed.len()];
+    Some(format!("{indent}font = {}", keep_size(old, family)))
+}
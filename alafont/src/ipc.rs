@@ -0,0 +1,107 @@
+use std::fs::File;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use toml_edit::{DocumentMut, Item};
+
+/// Whether alafont runs inside alacritty, whose IPC socket it can talk to
+pub fn in_alacritty() -> bool {
+    std::env::var_os("ALACRITTY_SOCKET").is_some()
+}
+
+/// Options for `alacritty msg config` with every value `new` sets differently from `old`
+pub fn changed_options(old: &str, new: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let old = old.parse::<DocumentMut>()?;
+    let new = new.parse::<DocumentMut>()?;
+
+    let mut options = Vec::new();
+    collect_changes(old.as_item(), new.as_item(), "", &mut options);
+    Ok(options)
+}
+
+fn collect_changes(old: &Item, new: &Item, path: &str, options: &mut Vec<String>) {
+    if let Some(table) = new.as_table_like() {
+        for (key, item) in table.iter() {
+            let path = if path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{path}.{key}")
+            };
+            let old = old.get(key).unwrap_or(&Item::None);
+            collect_changes(old, item, &path, options);
+        }
+    } else if let Some(value) = new.as_value() {
+        let value = value.to_string();
+        let old = old.as_value().map(ToString::to_string);
+        if old.as_deref().map(str::trim) != Some(value.trim()) {
+            options.push(format!("{path}={}", value.trim()));
+        }
+    }
+}
+
+/// Change the running alacritty's config without touching the file
+///
+/// `alacritty msg config --reset` goes back to the file's settings.
+pub fn set_config(options: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if !in_alacritty() {
+        return Err("--msg only works inside alacritty (ALACRITTY_SOCKET isn't set)".into());
+    }
+    if options.is_empty() {
+        return Ok(());
+    }
+
+    let status = Command::new("alacritty")
+        .args(["msg", "config"])
+        .args(options)
+        .status()
+        .map_err(|e| format!("Failed to run alacritty msg: {e}"))?;
+    if !status.success() {
+        return Err(format!("alacritty msg config failed: {status}").into());
+    }
+    Ok(())
+}
+
+/// Touch the config so alacritty's live reload sees it changed, then check
+/// the running alacritty picked up the font size
+pub fn reload(path: &Path, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    File::options()
+        .append(true)
+        .open(path)?
+        .set_modified(SystemTime::now())?;
+
+    let expected = content
+        .parse::<DocumentMut>()
+        .ok()
+        .and_then(|doc| doc.get("font")?.get("size")?.as_float());
+    let Some(expected) = expected.filter(|_| in_alacritty()) else {
+        return Ok(());
+    };
+
+    for _ in 0..10 {
+        std::thread::sleep(Duration::from_millis(100));
+        if running_font_size() == Some(expected) {
+            println!("alacritty reloaded {}", path.display());
+            return Ok(());
+        }
+    }
+
+    eprintln!(
+        "Warning: alacritty didn't reload {}, check that live_config_reload is on",
+        path.display()
+    );
+    Ok(())
+}
+
+/// Font size of the running alacritty, from `alacritty msg get-config`
+fn running_font_size() -> Option<f64> {
+    let output = Command::new("alacritty")
+        .args(["msg", "get-config"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let config: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    config["font"]["size"].as_f64()
+}
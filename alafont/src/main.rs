@@ -1,43 +1,784 @@
+mod apps;
+mod backup;
+mod config;
+mod fonts;
+mod integrations;
+mod ipc;
+mod pick;
+mod show;
+mod theme;
+mod yaml;
+
 use std::fs;
-use std::path::PathBuf;
-use clap::Parser;
-use toml_edit::{DocumentMut, value};
+use std::path::{Path, PathBuf};
+use clap::{Parser, Subcommand};
+use toml_edit::DocumentMut;
+use apps::{App, Font, Padding, Window};
+use pick::Picker;
 
 #[derive(Parser, Debug)]
-#[command(name = "alacritty_font")]
+#[command(
+    name = "alacritty_font",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    #[arg(short, long)]
-    font: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["opacity", "padding"],
+        requires = "size"
+    )]
+    font: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["opacity", "padding"],
+        requires = "font"
+    )]
+    size: Option<f64>,
+
+    /// Style of the normal face, e.g. Medium
+    #[arg(long, requires = "font")]
+    style: Option<String>,
+
+    /// Family for bold text [default: --font]
+    #[arg(long, requires = "font")]
+    bold_font: Option<String>,
+
+    /// Family for italic text [default: --font]
+    #[arg(long, requires = "font")]
+    italic_font: Option<String>,
 
-    #[arg(short, long)]
-    size: f64,
+    /// Family for bold italic text [default: --font]
+    #[arg(long, requires = "font")]
+    bold_italic_font: Option<String>,
 
-    #[arg(short, long)]
+    /// Families for glyphs the font doesn't have, comma separated
+    #[arg(long, value_delimiter = ',', requires = "font")]
+    fallback: Vec<String>,
+
+    /// Window background opacity, from 0 to 1
+    #[arg(long, value_parser = parse_opacity)]
+    opacity: Option<f64>,
+
+    /// Window padding in pixels, horizontal x vertical, e.g. 12x8
+    #[arg(long)]
+    padding: Option<Padding>,
+
+    /// Write fonts fontconfig doesn't know about
+    #[arg(long)]
+    force: bool,
+
+    /// Terminals to update, comma separated [default: every one with a config file]
+    #[arg(short, long, value_delimiter = ',', global = true)]
+    apps: Vec<App>,
+
+    /// Config file to edit, for a single app (alacritty unless --apps says otherwise)
+    #[arg(short, long, global = true)]
     config: Option<PathBuf>,
+
+    /// Print the changes as a diff instead of writing them
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Make alacritty reload the config and check that it did
+    #[arg(long, global = true, conflicts_with = "dry_run")]
+    reload: bool,
+
+    /// Change the running alacritty through `alacritty msg config` without saving
+    #[arg(long, global = true, conflicts_with_all = ["dry_run", "reload"])]
+    msg: bool,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+/// What to do with the edits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Write,
+    Reload,
+    DryRun,
+    Msg,
+}
 
-    let config_path = if let Some(path) = args.config {
-        path
-    } else {
-        let home = std::env::var("HOME")?;
-        PathBuf::from(home).join(".config/alacritty/alacritty.toml")
+impl Args {
+    fn mode(&self) -> Mode {
+        if self.dry_run {
+            Mode::DryRun
+        } else if self.msg {
+            Mode::Msg
+        } else if self.reload {
+            Mode::Reload
+        } else {
+            Mode::Write
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Apply a color scheme from the bundled or user themes
+    Theme {
+        #[arg(required_unless_present = "list")]
+        name: Option<String>,
+
+        /// List the available themes
+        #[arg(long)]
+        list: bool,
+    },
+    /// Switch to the next font of `favourites` in ~/.config/alafont/config.toml
+    Cycle {
+        /// Go to the previous font instead
+        #[arg(long)]
+        reverse: bool,
+
+        /// Write fonts fontconfig doesn't know about
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the current font and window settings of every config
+    Show {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Restore the configs from before the last change
+    Undo,
+    /// Convert a legacy alacritty.yml to alacritty.toml with `alacritty migrate`
+    Migrate,
+    /// Pick an installed monospace font, previewing each one
+    ///
+    /// Escape reverts the configs to the font they had before.
+    Pick {
+        /// Font size [default: the size each config already has]
+        #[arg(short, long)]
+        size: Option<f64>,
+
+        #[arg(long, value_enum, default_value_t)]
+        picker: Picker,
+
+        /// Apply one font and exit, run by the picker for the preview
+        #[arg(long, hide = true)]
+        apply: Option<String>,
+    },
+    /// Change the font size, keeping the font
+    ///
+    /// Sizes starting with + or - are relative to the current size, so
+    /// `size +1` and `size -0.5` adjust it while `size 12` sets it.
+    Size {
+        #[arg(
+            required_unless_present = "preset",
+            allow_hyphen_values = true,
+            value_parser = parse_size
+        )]
+        size: Option<Size>,
+
+        /// Named size from `[presets]` in ~/.config/alafont/config.toml
+        #[arg(long, conflicts_with = "size")]
+        preset: Option<String>,
+    },
+    /// Apply a profile from `[profiles]` in ~/.config/alafont/config.toml
+    ///
+    /// Every setting of the profile is written to every config at once.
+    Profile {
+        #[arg(required_unless_present = "list")]
+        name: Option<String>,
+
+        /// List the profiles
+        #[arg(long)]
+        list: bool,
+
+        /// Write fonts fontconfig doesn't know about
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Font size to write, relative to the size a config already has
+#[derive(Clone, Copy, Debug)]
+enum Size {
+    Keep,
+    Set(f64),
+    Adjust(f64),
+}
+
+impl Size {
+    fn resolve(self, current: Option<f64>) -> Option<f64> {
+        match self {
+            Self::Keep => current,
+            Self::Set(size) => Some(size),
+            Self::Adjust(delta) => current.map(|size| size + delta),
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<Size, String> {
+    let number = |value: &str| {
+        value
+            .parse::<f64>()
+            .map_err(|_| format!("'{value}' isn't a number"))
     };
 
-    let content = fs::read_to_string(&config_path)?;
-    let mut doc = content.parse::<DocumentMut>()?;
+    if let Some(delta) = value.strip_prefix('+') {
+        Ok(Size::Adjust(number(delta)?))
+    } else if value.starts_with('-') {
+        Ok(Size::Adjust(number(value)?))
+    } else {
+        Ok(Size::Set(number(value)?))
+    }
+}
+
+fn parse_opacity(value: &str) -> Result<f64, String> {
+    let opacity: f64 = value
+        .parse()
+        .map_err(|_| format!("'{value}' isn't a number"))?;
+    apps::check_opacity(opacity)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = Args::parse();
+    let mode = args.mode();
+    // The other apps have nothing like alacritty msg
+    if mode == Mode::Msg && args.apps.is_empty() {
+        args.apps = vec![App::Alacritty];
+    }
+
+    match args.command {
+        Some(Command::Theme { list: true, .. }) => {
+            for name in theme::list() {
+                println!("{name}");
+            }
+        }
+        Some(Command::Theme { name, .. }) => {
+            let name = name.ok_or("Theme name is required")?;
+            let config_path = match args.config {
+                Some(path) => path,
+                None => App::Alacritty
+                    .locate()
+                    .map(|(_, path)| path)
+                    .ok_or("No alacritty config found, pass one with --config")?,
+            };
+            if App::Alacritty.for_path(&config_path) == App::AlacrittyYaml {
+                return Err(
+                    "Themes need alacritty.toml, convert the YAML config with `alafont migrate`"
+                        .into(),
+                );
+            }
+
+            let content = fs::read_to_string(&config_path)?;
+            let mut doc = content.parse::<DocumentMut>()?;
+            theme::apply(&mut doc, &theme::load(&name)?);
+
+            let edit = Edit {
+                name: App::Alacritty.name(),
+                app: Some(App::Alacritty),
+                path: config_path,
+                old: content,
+                new: doc.to_string(),
+                size: None,
+            };
+            if write(std::slice::from_ref(&edit), mode)? {
+                println!("Applied theme '{name}'");
+            }
+        }
+        Some(Command::Migrate) => {
+            let config_path = match args.config {
+                Some(path) => path,
+                None => App::AlacrittyYaml
+                    .locate()
+                    .map(|(_, path)| path)
+                    .ok_or("No alacritty.yml found, pass one with --config")?,
+            };
+            migrate(&config_path, args.dry_run)?;
+        }
+        Some(Command::Cycle { reverse, force }) => {
+            let favourites = config::Config::load()?.favourites;
+            if favourites.is_empty() {
+                return Err("No favourites in ~/.config/alafont/config.toml".into());
+            }
+
+            // Start from the first favourite the first time round
+            let index = match cycle_index()? {
+                Some(index) if reverse => (index + favourites.len() - 1) % favourites.len(),
+                Some(index) => (index + 1) % favourites.len(),
+                None => 0,
+            };
+            let font = Font::new(&favourites[index]);
+            fonts::check(&font, force)?;
+
+            let targets = targets(&args.apps, args.config)?;
+            let mut edits = set_font(&targets, Some(&font), Size::Keep, &Window::default())?;
+            edits.extend(follow_font(&font.family)?);
+            write(&edits, mode)?;
+            if mode != Mode::DryRun {
+                let state = config::data_dir()?;
+                fs::create_dir_all(&state)?;
+                fs::write(state.join("cycle"), index.to_string())?;
+                if mode != Mode::Msg {
+                    print_updated(&edits, Some(&font.family));
+                }
+            }
+        }
+        Some(Command::Show { json }) => {
+            let mut settings = Vec::new();
+            for (app, config_path) in targets(&args.apps, args.config)? {
+                let content = fs::read_to_string(&config_path)
+                    .map_err(|e| format!("Failed to read {}: {e}", config_path.display()))?;
+                settings.push((app, app.current(&content)));
+            }
+
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&show::to_json(&settings))?
+                );
+            } else {
+                show::print_table(&settings);
+            }
+        }
+        Some(Command::Undo) => match mode {
+            Mode::DryRun => {
+                write(&undo_edits()?, mode)?;
+            }
+            Mode::Msg => return Err("undo restores the config files, it can't use --msg".into()),
+            Mode::Write | Mode::Reload => {
+                let edits = undo_edits()?;
+                for path in backup::undo()? {
+                    println!("Restored {}", path.display());
+                }
+                if mode == Mode::Reload {
+                    reload(&edits)?;
+                }
+            }
+        },
+        Some(Command::Pick {
+            size,
+            apply: Some(font),
+            ..
+        }) => {
+            // Previews aren't backed up, the picker restores the configs on cancel
+            let size = size.map_or(Size::Keep, Size::Set);
+            let font = Font::new(&font);
+            let targets = targets(&args.apps, args.config)?;
+            for edit in set_font(&targets, Some(&font), size, &Window::default())? {
+                fs::write(&edit.path, &edit.new)?;
+            }
+        }
+        Some(Command::Pick { size, picker, .. }) => {
+            let targets = targets(&args.apps, args.config.clone())?;
+            let fonts = fonts::monospace()?;
+            if fonts.is_empty() {
+                return Err("fc-list found no monospace fonts".into());
+            }
+
+            // The picker runs alafont itself for each highlighted font
+            let mut preview = vec![
+                pick::shell_quote(&std::env::current_exe()?.to_string_lossy()),
+                String::from("pick --apply {}"),
+            ];
+            let apps: Vec<&str> = targets.iter().map(|(app, _)| app.name()).collect();
+            preview.push(format!("--apps {}", apps.join(",")));
+            if let Some(config) = &args.config {
+                preview.push(format!(
+                    "--config {}",
+                    pick::shell_quote(&config.to_string_lossy())
+                ));
+            }
+            if let Some(size) = size {
+                preview.push(format!("--size {size}"));
+            }
 
-    doc["font"]["size"] = value(args.size);
+            // Previews write the configs, which a dry run mustn't
+            let preview = preview.join(" ");
+            let preview = (mode != Mode::DryRun).then_some(preview.as_str());
+            let snapshot = pick::Snapshot::new(&targets)?;
+            match pick::run(picker, &fonts, preview)? {
+                Some(font) => {
+                    // Go back to the configs from before the previews, so the
+                    // backup and diff are of the actual change
+                    if preview.is_some() {
+                        snapshot.restore()?;
+                    }
 
-    doc["font"]["normal"]["family"] = value(&args.font);
-    doc["font"]["bold"]["family"] = value(&args.font);
-    doc["font"]["italic"]["family"] = value(&args.font);
+                    let size = size.map_or(Size::Keep, Size::Set);
+                    let font = Font::new(&font);
+                    let mut edits = set_font(&targets, Some(&font), size, &Window::default())?;
+                    edits.extend(follow_font(&font.family)?);
+                    if write(&edits, mode)? {
+                        print_updated(&edits, Some(&font.family));
+                    }
+                }
+                None => {
+                    if preview.is_some() {
+                        snapshot.restore()?;
+                    }
+                    println!("Cancelled, font left as it was");
+                }
+            }
+        }
+        Some(Command::Size { size, preset }) => {
+            let size = match (size, preset) {
+                (Some(size), _) => size,
+                (None, Some(preset)) => {
+                    let config = config::Config::load()?;
+                    let size = config.presets.get(&preset).ok_or_else(|| {
+                        if config.presets.is_empty() {
+                            return format!(
+                                "Unknown preset '{preset}', none in ~/.config/alafont/config.toml"
+                            );
+                        }
+                        let known: Vec<&str> = config.presets.keys().map(String::as_str).collect();
+                        format!("Unknown preset '{preset}', known: {}", known.join(", "))
+                    })?;
+                    Size::Set(*size)
+                }
+                (None, None) => return Err("A size or --preset is required".into()),
+            };
 
-    fs::write(&config_path, doc.to_string())?;
+            let targets = targets(&args.apps, args.config)?;
+            let edits = set_font(&targets, None, size, &Window::default())?;
+            if write(&edits, mode)? {
+                print_updated(&edits, None);
+            }
+        }
+        Some(Command::Profile { list: true, .. }) => {
+            for name in config::Config::load()?.profiles.keys() {
+                println!("{name}");
+            }
+        }
+        Some(Command::Profile { name, force, .. }) => {
+            let name = name.ok_or("Profile name is required")?;
+            let mut config = config::Config::load()?;
+            let profile = config.profiles.remove(&name).ok_or_else(|| {
+                let known: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+                format!("Unknown profile '{name}', known: {}", known.join(", "))
+            })?;
 
-    println!("Updated font to '{}' with size {}", args.font, args.size);
+            let font = profile.font.as_deref().map(Font::new);
+            if let Some(font) = &font {
+                fonts::check(font, force)?;
+            }
+            let size = profile.size.map_or(Size::Keep, Size::Set);
+            let window = Window {
+                opacity: profile.opacity,
+                padding: profile.padding,
+            };
+
+            let targets = targets(&args.apps, args.config)?;
+            let mut edits = set_font(&targets, font.as_ref(), size, &window)?;
+            if let Some(font) = &font {
+                edits.extend(follow_font(&font.family)?);
+            }
+            if write(&edits, mode)? {
+                let apps: Vec<&str> = edits.iter().map(|edit| edit.name).collect();
+                println!("Applied profile '{name}' to {}", apps.join(", "));
+            }
+        }
+        None => {
+            let font = args.font.map(|family| Font {
+                family,
+                style: args.style,
+                bold: args.bold_font,
+                italic: args.italic_font,
+                bold_italic: args.bold_italic_font,
+                fallback: args.fallback,
+            });
+            if let Some(font) = &font {
+                fonts::check(font, args.force)?;
+            }
+            let size = args.size.map_or(Size::Keep, Size::Set);
+            let window = Window {
+                opacity: args.opacity,
+                padding: args.padding,
+            };
+
+            let targets = targets(&args.apps, args.config)?;
+            let mut edits = set_font(&targets, font.as_ref(), size, &window)?;
+            if let Some(font) = &font {
+                edits.extend(follow_font(&font.family)?);
+            }
+            if write(&edits, mode)? {
+                print_updated(&edits, font.as_ref().map(|font| font.family.as_str()));
+            }
+        }
+    }
 
     Ok(())
 }
+
+/// New contents for an app's config, or for another tool's in `[integrations]`
+struct Edit {
+    name: &'static str,
+    app: Option<App>,
+    path: PathBuf,
+    old: String,
+    new: String,
+    /// Font size the config ends up with, for font changes
+    size: Option<f64>,
+}
+
+/// Font and window changes for every target config, with `size` relative
+/// to each one's current size, keeping the fonts when `font` is `None`
+fn set_font(
+    targets: &[(App, PathBuf)],
+    font: Option<&Font>,
+    size: Size,
+    window: &Window,
+) -> Result<Vec<Edit>, Box<dyn std::error::Error>> {
+    let mut edits = Vec::new();
+
+    for (app, config_path) in targets {
+        let content = fs::read_to_string(config_path)
+            .map_err(|e| format!("Failed to read {}: {e}", config_path.display()))?;
+
+        let mut new = content.clone();
+        let mut new_size = None;
+        if font.is_some() || !matches!(size, Size::Keep) {
+            let size = size.resolve(app.font_size(&content)).ok_or_else(|| {
+                format!(
+                    "No font size set in {}, pass an absolute size",
+                    config_path.display()
+                )
+            })?;
+            if size <= 0.0 {
+                return Err(format!("Font size {size} is too small").into());
+            }
+
+            new = app.set_font(&new, font, size)?;
+            new_size = Some(size);
+        }
+        if !window.is_empty() {
+            new = app.set_window(&new, window)?;
+        }
+
+        edits.push(Edit {
+            name: app.name(),
+            app: Some(*app),
+            path: config_path.clone(),
+            old: content,
+            new,
+            size: new_size,
+        });
+    }
+
+    Ok(edits)
+}
+
+/// Font changes for the other tools' configs in `[integrations]`, which keep
+/// their own font sizes
+fn follow_font(family: &str) -> Result<Vec<Edit>, Box<dyn std::error::Error>> {
+    let mut edits = Vec::new();
+
+    for integration in config::Config::load()?.integrations {
+        let (tool, path) = (integration.tool, integration.path);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!(
+                    "{}: skipped, failed to read {}: {e}",
+                    tool.name(),
+                    path.display()
+                );
+                continue;
+            }
+        };
+        let Some(new) = tool.set_font(&content, family) else {
+            eprintln!(
+                "{}: skipped, no font setting in {}",
+                tool.name(),
+                path.display()
+            );
+            continue;
+        };
+
+        edits.push(Edit {
+            name: tool.name(),
+            app: None,
+            path,
+            old: content,
+            new,
+            size: None,
+        });
+    }
+
+    Ok(edits)
+}
+
+/// Write the edits after backing up the old configs, or apply them as `mode` says
+///
+/// Returns whether the config files were written.
+fn write(edits: &[Edit], mode: Mode) -> Result<bool, Box<dyn std::error::Error>> {
+    match mode {
+        Mode::DryRun => {
+            for edit in edits {
+                let path = edit.path.to_string_lossy();
+                let diff = similar::TextDiff::from_lines(&edit.old, &edit.new);
+                print!("{}", diff.unified_diff().header(&path, &path));
+            }
+            return Ok(false);
+        }
+        Mode::Msg => {
+            for edit in edits {
+                if edit.app == Some(App::AlacrittyYaml) {
+                    eprintln!("alacritty: skipped, --msg needs alacritty.toml");
+                    continue;
+                }
+                if edit.app != Some(App::Alacritty) {
+                    eprintln!("{}: skipped, --msg only changes alacritty", edit.name);
+                    continue;
+                }
+                let options = ipc::changed_options(&edit.old, &edit.new)?;
+                ipc::set_config(&options)?;
+                println!("Changed the running alacritty: {}", options.join(" "));
+            }
+            return Ok(false);
+        }
+        Mode::Write | Mode::Reload => {}
+    }
+
+    let originals: Vec<(&Path, &str)> = edits
+        .iter()
+        .map(|edit| (edit.path.as_path(), edit.old.as_str()))
+        .collect();
+    backup::save(&originals)?;
+
+    for edit in edits {
+        fs::write(&edit.path, &edit.new)
+            .map_err(|e| format!("Failed to write {}: {e}", edit.path.display()))?;
+    }
+
+    if mode == Mode::Reload {
+        reload(edits)?;
+    }
+
+    Ok(true)
+}
+
+/// Make alacritty reload the edited alacritty configs and check that it did
+fn reload(edits: &[Edit]) -> Result<(), Box<dyn std::error::Error>> {
+    for edit in edits
+        .iter()
+        .filter(|edit| matches!(edit.app, Some(App::Alacritty | App::AlacrittyYaml)))
+    {
+        ipc::reload(&edit.path, &edit.new)?;
+    }
+    Ok(())
+}
+
+/// What `undo` would change, from the current configs back to the newest backup
+fn undo_edits() -> Result<Vec<Edit>, Box<dyn std::error::Error>> {
+    let detected: Vec<(App, PathBuf)> = App::ALL.iter().filter_map(|app| app.locate()).collect();
+
+    let mut edits = Vec::new();
+    for (path, content) in backup::latest()? {
+        // Backups don't say which app a config is for, so go by where it is
+        let app = detected
+            .iter()
+            .find(|(_, detected)| *detected == path)
+            .map(|(app, _)| *app)
+            .or_else(|| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("alacritty."))
+                    .then(|| App::Alacritty.for_path(&path))
+            });
+
+        edits.push(Edit {
+            name: app.map_or("config", App::name),
+            app,
+            old: fs::read_to_string(&path).unwrap_or_default(),
+            new: content,
+            path,
+            size: None,
+        });
+    }
+
+    Ok(edits)
+}
+
+fn print_updated(edits: &[Edit], font: Option<&str>) {
+    for edit in edits {
+        let app = edit.name;
+        match (font, edit.size) {
+            (Some(font), None) if edit.app.is_none() => println!("Updated {app} font to '{font}'"),
+            (Some(font), Some(size)) => {
+                println!("Updated {app} font to '{font}' with size {size}");
+            }
+            (None, Some(size)) => println!("Updated {app} font size to {size}"),
+            (_, None) => println!("Updated {app} window settings"),
+        }
+    }
+}
+
+/// Index of the favourite `alafont cycle` last switched to
+fn cycle_index() -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    let path = config::data_dir()?.join("cycle");
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(fs::read_to_string(path)?.trim().parse().ok())
+}
+
+/// Convert an alacritty.yml to TOML, next to it
+///
+/// alacritty knows its own settings best, including the ones renamed since
+/// the YAML days, so this leaves the conversion to `alacritty migrate`.
+fn migrate(path: &Path, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let toml = path.with_file_name("alacritty.toml");
+    if toml.exists() && !dry_run {
+        return Err(format!("{} already exists", toml.display()).into());
+    }
+
+    let mut command = std::process::Command::new("alacritty");
+    command.args(["migrate", "--config-file"]).arg(path);
+    if dry_run {
+        command.arg("--dry-run");
+    }
+
+    let status = command.status().map_err(|e| {
+        format!("Failed to run alacritty migrate, it needs alacritty 0.13 or newer: {e}")
+    })?;
+    if !status.success() {
+        return Err(format!("alacritty migrate failed: {status}").into());
+    }
+
+    if !dry_run {
+        println!("Converted {} to {}", path.display(), toml.display());
+    }
+    Ok(())
+}
+
+/// Apps to update with their config files
+///
+/// Without `--apps`, every app with a config file is updated, or just
+/// alacritty when `--config` is given.
+fn targets(
+    apps: &[App],
+    config: Option<PathBuf>,
+) -> Result<Vec<(App, PathBuf)>, Box<dyn std::error::Error>> {
+    if let Some(path) = config {
+        return match apps {
+            [] => Ok(vec![(App::Alacritty.for_path(&path), path)]),
+            [app] => Ok(vec![(app.for_path(&path), path)]),
+            _ => Err("--config can only be used with a single app".into()),
+        };
+    }
+
+    if apps.is_empty() {
+        let detected: Vec<(App, PathBuf)> =
+            App::ALL.iter().filter_map(|app| app.locate()).collect();
+        if detected.is_empty() {
+            return Err("No terminal configs found, pass one with --config".into());
+        }
+        return Ok(detected);
+    }
+
+    apps.iter()
+        .map(|app| {
+            app.locate()
+                .ok_or_else(|| format!("No {} config found", app.name()).into())
+        })
+        .collect()
+}
@@ -0,0 +1,89 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use clap::ValueEnum;
+use crate::apps::App;
+
+/// Fuzzy finders `alafont pick` can run
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Picker {
+    #[default]
+    Fzf,
+    Skim,
+}
+
+/// Let the user pick a font, previewing each one as it's highlighted
+///
+/// `preview` is the command fzf runs for the highlighted font, with `{}` in
+/// place of the family, or `None` for no preview. skim has no focus events,
+/// so it only applies the final pick. Returns `None` when the picker was
+/// cancelled.
+pub fn run(
+    picker: Picker,
+    fonts: &[String],
+    preview: Option<&str>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let mut command = match picker {
+        Picker::Fzf => {
+            let mut command = Command::new("fzf");
+            command.args(["--prompt=font> ", "--no-multi"]);
+            if let Some(preview) = preview {
+                command.arg(format!("--bind=focus:execute-silent({preview})"));
+            }
+            command
+        }
+        Picker::Skim => {
+            let mut command = Command::new("sk");
+            command.args(["--prompt=font> ", "--no-multi"]);
+            command
+        }
+    };
+
+    let name = match picker {
+        Picker::Fzf => "fzf",
+        Picker::Skim => "sk",
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {name}: {e}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all((fonts.join("\n") + "\n").as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let font = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((!font.is_empty()).then_some(font))
+}
+
+/// Contents of the configs before picking, to put back on cancel
+pub struct Snapshot(Vec<(PathBuf, String)>);
+
+impl Snapshot {
+    pub fn new(targets: &[(App, PathBuf)]) -> Result<Self, Box<dyn std::error::Error>> {
+        let files = targets
+            .iter()
+            .map(|(_, path)| Ok((path.clone(), fs::read_to_string(path)?)))
+            .collect::<Result<_, std::io::Error>>()?;
+        Ok(Self(files))
+    }
+
+    pub fn restore(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for (path, content) in &self.0 {
+            fs::write(path, content)?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote an argument for the shell fzf runs its bindings with
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
@@ -0,0 +1,65 @@
+use crate::apps::{App, Current};
+use serde_json::json;
+
+/// Print the settings of every config as a table
+pub fn print_table(settings: &[(App, Current)]) {
+    let none = || String::from("-");
+
+    let mut rows = vec![
+        [
+            "APP", "FONT", "BOLD", "ITALIC", "SIZE", "OPACITY", "PADDING",
+        ]
+        .map(String::from),
+    ];
+    for (app, current) in settings {
+        rows.push([
+            app.name().to_string(),
+            current.font.clone().unwrap_or_else(none),
+            current.bold.clone().unwrap_or_else(none),
+            current.italic.clone().unwrap_or_else(none),
+            current.size.map_or_else(none, |size| size.to_string()),
+            current
+                .opacity
+                .map_or_else(none, |opacity| opacity.to_string()),
+            current
+                .padding
+                .map_or_else(none, |padding| padding.to_string()),
+        ]);
+    }
+
+    let mut widths = [0; 7];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+/// The settings of every config as JSON, keyed by app
+pub fn to_json(settings: &[(App, Current)]) -> serde_json::Value {
+    let apps: serde_json::Map<String, serde_json::Value> = settings
+        .iter()
+        .map(|(app, current)| {
+            let value = json!({
+                "font": current.font,
+                "bold": current.bold,
+                "italic": current.italic,
+                "bold_italic": current.bold_italic,
+                "size": current.size,
+                "opacity": current.opacity,
+                "padding": current.padding.map(|padding| json!({ "x": padding.x, "y": padding.y })),
+            });
+            (app.name().to_string(), value)
+        })
+        .collect();
+    serde_json::Value::Object(apps)
+}
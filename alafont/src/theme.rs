@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::PathBuf;
+use toml_edit::{DocumentMut, Item};
+use crate::config;
+
+/// Themes shipped with alafont, overridden by user themes of the same name
+const BUNDLED_THEMES: &[(&str, &str)] = &[
+    ("dracula", include_str!("themes/dracula.toml")),
+    ("gruvbox-dark", include_str!("themes/gruvbox-dark.toml")),
+    ("nord", include_str!("themes/nord.toml")),
+    ("solarized-dark", include_str!("themes/solarized-dark.toml")),
+];
+
+/// User themes live in `~/.config/alafont/themes/<name>.toml`
+fn user_theme_dir() -> Option<PathBuf> {
+    config::dir().map(|dir| dir.join("themes"))
+}
+
+/// Load a theme fragment by name, preferring the user's theme directory
+pub fn load(name: &str) -> Result<DocumentMut, Box<dyn std::error::Error>> {
+    if let Some(path) = user_theme_dir().map(|dir| dir.join(format!("{name}.toml")))
+        && path.exists()
+    {
+        let content = fs::read_to_string(&path)?;
+        return Ok(content
+            .parse::<DocumentMut>()
+            .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?);
+    }
+
+    let (_, content) = BUNDLED_THEMES
+        .iter()
+        .find(|(bundled, _)| *bundled == name)
+        .ok_or_else(|| format!("Unknown theme '{name}', see `alafont theme --list`"))?;
+
+    Ok(content.parse::<DocumentMut>()?)
+}
+
+/// Apply a theme to the config
+///
+/// Every top-level table the theme defines (usually just `colors`) replaces the
+/// one in the config, so nothing from the previous theme is left behind.
+pub fn apply(doc: &mut DocumentMut, theme: &DocumentMut) {
+    for (key, item) in theme.iter() {
+        let mut item = item.clone();
+        reposition(&mut item);
+        doc[key] = item;
+    }
+}
+
+/// Drop the positions tables had in the theme file so they're written after
+/// the rest of the config, each separated by a blank line
+fn reposition(item: &mut Item) {
+    if let Some(table) = item.as_table_mut() {
+        table.set_position(None);
+        if !table.is_implicit() {
+            table.decor_mut().set_prefix("\n");
+        }
+        for (_, child) in table.iter_mut() {
+            reposition(child);
+        }
+    }
+}
+
+/// Names of the bundled and user themes, sorted
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = BUNDLED_THEMES
+        .iter()
+        .map(|(name, _)| (*name).to_string())
+        .collect();
+
+    if let Some(entries) = user_theme_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "toml")
+                && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+            {
+                names.push(stem.to_string());
+            }
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
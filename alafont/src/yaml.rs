@@ -0,0 +1,156 @@
+//! Just enough YAML editing for the font and window settings of a legacy
+//! alacritty.yml: block mappings indented with spaces, scalars on the key's
+//! own line. Everything else in the file is kept as it is, comments included.
+
+/// Indentation of a line that holds a key, `None` for blank lines and comments
+fn indent(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    Some(line.len() - trimmed.len())
+}
+
+/// Where a trailing comment starts in `line`, at the space before its `#`
+///
+/// A `#` inside a quoted scalar, as in `"Foo #2"`, isn't a comment.
+fn comment_start(line: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut prev = ' ';
+    for (at, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && prev == ' ' => return Some(at.saturating_sub(1)),
+            None => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+/// The value after `key:` when `line` sets `key`
+fn key_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line.trim_start().strip_prefix(key)?.strip_prefix(':')?;
+    (rest.is_empty() || rest.starts_with(' ')).then(|| rest.trim())
+}
+
+/// Line of `key` among the entries of the block `lines[start..end]`, with
+/// the block's indentation
+fn find(lines: &[String], start: usize, end: usize, key: &str) -> (Option<usize>, Option<usize>) {
+    let block_indent = lines[start..end].iter().find_map(|l| indent(l));
+
+    let found = (start..end)
+        .find(|&i| indent(&lines[i]) == block_indent && key_value(&lines[i], key).is_some());
+    (found, block_indent)
+}
+
+/// Where the block of the entry on line `i` ends
+fn block_end(lines: &[String], i: usize, end: usize) -> usize {
+    let own = indent(&lines[i]).unwrap_or(0);
+    (i + 1..end)
+        .find(|&j| indent(&lines[j]).is_some_and(|n| n <= own))
+        .unwrap_or(end)
+}
+
+/// The scalar at `path`, e.g. `["font", "size"]`
+pub fn get<'a>(content: &'a str, path: &[&str]) -> Option<&'a str> {
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let (mut start, mut end) = (0, lines.len());
+
+    for (depth, key) in path.iter().enumerate() {
+        let i = find(&lines, start, end, key).0?;
+        if depth == path.len() - 1 {
+            let value = key_value(content.lines().nth(i)?, key)?;
+            // Drop a trailing comment
+            let value = comment_start(value).map_or(value, |at| &value[..at]);
+            return Some(value.trim());
+        }
+        (start, end) = (i + 1, block_end(&lines, i, end));
+    }
+    None
+}
+
+/// Set the scalar at `path` to `value`, adding the mappings on the way if needed
+pub fn set(content: &str, path: &[&str], value: &str) -> Result<String, String> {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let (mut start, mut end) = (0, lines.len());
+    let mut parent_indent: Option<usize> = None;
+
+    for (depth, key) in path.iter().enumerate() {
+        let (found, block_indent) = find(&lines, start, end, key);
+
+        match found {
+            Some(i) if depth == path.len() - 1 => {
+                let own = indent(&lines[i]).unwrap_or(0);
+                let comment = comment_start(&lines[i]).map(|at| lines[i][at..].to_string());
+                lines[i] = format!("{}{key}: {value}", " ".repeat(own));
+                lines[i].push_str(comment.as_deref().unwrap_or_default());
+                return Ok(lines.join("\n") + "\n");
+            }
+            Some(i) => {
+                let rest = key_value(&lines[i], key).unwrap_or_default();
+                if !rest.is_empty() && !rest.starts_with('#') {
+                    return Err(format!(
+                        "'{}' in alacritty.yml isn't a block mapping, alafont can't change it",
+                        path[..=depth].join(".")
+                    ));
+                }
+                parent_indent = indent(&lines[i]);
+                (start, end) = (i + 1, block_end(&lines, i, end));
+            }
+            None => {
+                // Add the rest of the path at the end of the block
+                let base = block_indent.unwrap_or_else(|| parent_indent.map_or(0, |n| n + 2));
+                let at = (start..end)
+                    .rev()
+                    .find(|&i| indent(&lines[i]).is_some())
+                    .map_or(start, |i| i + 1);
+
+                let new = path[depth..].iter().enumerate().map(|(level, key)| {
+                    let pad = " ".repeat(base + 2 * level);
+                    if depth + level == path.len() - 1 {
+                        format!("{pad}{key}: {value}")
+                    } else {
+                        format!("{pad}{key}:")
+                    }
+                });
+                lines.splice(at..at, new);
+                return Ok(lines.join("\n") + "\n");
+            }
+        }
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Remove the entry at `path`, along with its block if it has one
+pub fn remove(content: &str, path: &[&str]) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let (mut start, mut end) = (0, lines.len());
+
+    for (depth, key) in path.iter().enumerate() {
+        let Some(i) = find(&lines, start, end, key).0 else {
+            return content.to_string();
+        };
+        if depth == path.len() - 1 {
+            let block = block_end(&lines, i, end);
+            lines.drain(i..block);
+            return lines.join("\n") + "\n";
+        }
+        (start, end) = (i + 1, block_end(&lines, i, end));
+    }
+    content.to_string()
+}
+
+/// A YAML double-quoted string, whose escapes JSON shares
+///
+/// Every string is written this way, so values with `:` or `#` in them, like
+/// a "Foo: Mono" family, stay a single scalar.
+pub fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("\"{value}\""))
+}
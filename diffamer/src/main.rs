@@ -3,9 +3,27 @@ use clap::Parser;
 use console::style;
 use similar::{ChangeTag, TextDiff};
 use std::collections::HashSet;
-use std::fs;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::{self, fs::MetadataExt};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for a shell to release a history file, older locks are
+/// stale and removed, as zsh does
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Suffix of the lock files zsh keeps next to a history file while writing it
+const LOCK_SUFFIX: &str = ".LOCK";
+
+/// Whether `path` is a lock file, which is never merged or copied
+fn is_lock(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| name.ends_with(LOCK_SUFFIX))
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, arg_required_else_help = true)]
@@ -36,6 +54,91 @@ enum FileStatus {
     Unchanged,
 }
 
+/// Exclusive access to a local file while it's merged
+///
+/// Holds zsh's `<file>.LOCK` file, which zsh creates before writing its
+/// HISTFILE, and removes it on drop.
+struct FileLock {
+    lock_path: PathBuf,
+}
+
+impl FileLock {
+    fn acquire(path: &Path) -> Result<Self> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(LOCK_SUFFIX);
+        let lock_path = PathBuf::from(lock_path);
+
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(mut lock) => {
+                    let _ = writeln!(lock, "{}", std::process::id());
+                    return Ok(Self { lock_path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    // Left behind by a shell that died while writing
+                    let stale = fs::metadata(&lock_path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > LOCK_TIMEOUT);
+                    if stale {
+                        let _ = fs::remove_file(&lock_path);
+                        continue;
+                    }
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        anyhow::bail!(
+                            "Timed out waiting for {}, remove it if no shell is writing",
+                            lock_path.display()
+                        );
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to create {}", lock_path.display()));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.lock_path);
+    }
+}
+
+/// Replace `path` with `content` through a temp file in the same directory,
+/// so readers never see it half-written
+///
+/// A symlinked `path`, as dotfile managers leave them, is followed and its
+/// target replaced, keeping the link.
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.as_path();
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut temp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create temp file in {}", dir.display()))?;
+    temp.write_all(content.as_bytes())?;
+    temp.as_file().sync_all()?;
+
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(temp.path(), metadata.permissions())?;
+        unix::fs::chown(temp.path(), Some(metadata.uid()), Some(metadata.gid()))
+            .with_context(|| format!("Failed to keep the owner of {}", path.display()))?;
+    }
+
+    temp.persist(path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
 #[derive(Debug)]
 struct FileSyncWorker {
     host_alias: String,
@@ -107,12 +210,15 @@ impl FileSyncWorker {
             let entry = entry?;
             let path = entry.path();
 
-            if path.is_file() {
+            if path.is_file() && !is_lock(&path) {
                 match self.process_files(&path) {
                     Ok(FileStatus::Created) => created += 1,
                     Ok(FileStatus::Updated) => updated += 1,
                     Ok(FileStatus::Unchanged) => unchanged += 1,
-                    Err(_) => errors += 1,
+                    Err(e) => {
+                        eprintln!("{}: {e:#}", path.display());
+                        errors += 1;
+                    }
                 }
             }
         }
@@ -143,12 +249,15 @@ impl FileSyncWorker {
             let entry = entry?;
             let path = entry.path();
 
-            if path.is_file() {
+            if path.is_file() && !is_lock(&path) {
                 match self.process_local_file(&path, remote_temp_path, staging_path) {
                     Ok(FileStatus::Created) => created += 1,
                     Ok(FileStatus::Updated) => updated += 1,
                     Ok(FileStatus::Unchanged) => unchanged += 1,
-                    Err(_) => errors += 1,
+                    Err(e) => {
+                        eprintln!("{}: {e:#}", path.display());
+                        errors += 1;
+                    }
                 }
             }
         }
@@ -162,7 +271,11 @@ impl FileSyncWorker {
         }
 
         if self.sync {
-            println!("Uploading to {}:{}/", self.host_alias, self.remote_path.display());
+            println!(
+                "Uploading to {}:{}/",
+                self.host_alias,
+                self.remote_path.display()
+            );
             let remote_dest = format!("{}:{}/", self.host_alias, self.remote_path.display());
             let status = Command::new("rsync")
                 .arg("-az")
@@ -272,6 +385,13 @@ impl FileSyncWorker {
 
     fn merge_and_write(&self, filename: &str, remote_entries: Vec<String>) -> Result<FileStatus> {
         let local_files = self.local_path.join(filename);
+        // Hold the lock from reading the file until it's replaced, so lines a
+        // shell appends in between aren't lost
+        let _lock = if self.sync {
+            Some(FileLock::acquire(&local_files)?)
+        } else {
+            None
+        };
         let exists = local_files.exists();
 
         let final_entries = if exists {
@@ -330,7 +450,7 @@ impl FileSyncWorker {
         };
 
         if !new_content.is_empty() || exists {
-            fs::write(&local_files, new_content)?;
+            write_atomic(&local_files, &new_content)?;
         }
 
         Ok(status)
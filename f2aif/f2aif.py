@@ -1,8 +1,150 @@
 #!/usr/bin/env python3
 import os
+import re
+import shutil
 import subprocess
 import argparse
+from datetime import datetime
 from pathlib import Path
+from urllib.parse import quote
+
+IGNORE_FILE = '.f2aifignore'
+
+# Target loudness of previews in LUFS, about what streaming services use
+PREVIEW_LOUDNESS = -14
+PREVIEW_CODECS = {
+    'mp3': ('libmp3lame', '.mp3'),
+    'aac': ('aac', '.m4a'),
+}
+
+def glob_to_regex(pattern):
+    """Translate a glob into a regex over '/'-separated relative paths.
+
+    `**/` matches any number of directories, `*` and `?` stay within one path
+    component. Patterns without a '/' match the name of any component.
+    """
+    anchored = '/' in pattern.rstrip('/')
+    pattern = pattern.strip('/')
+    regex = ''
+    i = 0
+    while i < len(pattern):
+        if pattern.startswith('**/', i):
+            regex += '(?:.*/)?'
+            i += 3
+        elif pattern.startswith('**', i):
+            regex += '.*'
+            i += 2
+        elif pattern[i] == '*':
+            regex += '[^/]*'
+            i += 1
+        elif pattern[i] == '?':
+            regex += '[^/]'
+            i += 1
+        else:
+            regex += re.escape(pattern[i])
+            i += 1
+    if not anchored:
+        regex = '(?:.*/)?' + regex
+    # A match on a directory excludes everything below it
+    return re.compile(regex + '(?:/.*)?')
+
+def read_ignore_file(start_dir):
+    """Patterns from the .f2aifignore file in start_dir, if there is one."""
+    ignore_file = start_dir / IGNORE_FILE
+    if not ignore_file.is_file():
+        return []
+    lines = ignore_file.read_text().splitlines()
+    return [line.strip() for line in lines if line.strip() and not line.startswith('#')]
+
+def collect_flac_files(start_dir, excludes=()):
+    """Find FLAC files below start_dir, leaving out those matching an exclude pattern."""
+    patterns = [glob_to_regex(p) for p in [*excludes, *read_ignore_file(start_dir)]]
+    files = []
+    for flac_file in sorted(start_dir.rglob('*.flac')):
+        relative = flac_file.relative_to(start_dir).as_posix()
+        if any(p.fullmatch(relative) for p in patterns):
+            print(f"Excluding {flac_file}")
+            continue
+        files.append(flac_file)
+    return files
+
+def xdg_trash_dir():
+    """The home trash of the freedesktop.org trash spec."""
+    data_home = os.environ.get('XDG_DATA_HOME') or Path.home() / '.local' / 'share'
+    return Path(data_home) / 'Trash'
+
+def mount_point(path):
+    """The top directory of the filesystem path is on."""
+    path = path.absolute()
+    dev = path.lstat().st_dev
+    while path.parent != path and path.parent.lstat().st_dev == dev:
+        path = path.parent
+    return path
+
+def trash_dir_for(path):
+    """The trash a file goes to, and the directory its recorded path is relative to.
+
+    Files on another filesystem than the home trash go to the trash at the top
+    of their own, as the spec asks, rather than being copied across.
+    """
+    home_trash = xdg_trash_dir()
+    existing = home_trash
+    while not existing.exists():
+        existing = existing.parent
+    if existing.stat().st_dev == path.lstat().st_dev:
+        return home_trash, None
+
+    topdir = mount_point(path)
+    uid = os.getuid()
+    # A shared .Trash has to be sticky and not a symlink to be used
+    shared = topdir / '.Trash'
+    if shared.is_dir() and not shared.is_symlink() and shared.stat().st_mode & 0o1000:
+        return shared / str(uid), topdir
+    return topdir / f".Trash-{uid}", topdir
+
+def move_to_trash(path):
+    """Move a file to the XDG trash, so the desktop's trash can restore it."""
+    trash, topdir = trash_dir_for(path)
+    files_dir = trash / 'files'
+    info_dir = trash / 'info'
+    files_dir.mkdir(mode=0o700, parents=True, exist_ok=True)
+    info_dir.mkdir(mode=0o700, parents=True, exist_ok=True)
+
+    # Top directory trashes record paths relative to the top directory
+    original = path.absolute()
+    if topdir:
+        original = original.relative_to(topdir)
+
+    # Claim a name by creating its .trashinfo exclusively, as the spec asks
+    name = path.name
+    counter = 1
+    while True:
+        info_path = info_dir / f"{name}.trashinfo"
+        try:
+            with open(info_path, 'x') as info:
+                info.write('[Trash Info]\n')
+                info.write(f"Path={quote(str(original))}\n")
+                info.write(f"DeletionDate={datetime.now().strftime('%Y-%m-%dT%H:%M:%S')}\n")
+            break
+        except FileExistsError:
+            counter += 1
+            name = f"{path.stem}.{counter}{path.suffix}"
+
+    try:
+        shutil.move(str(path), files_dir / name)
+    except Exception:
+        info_path.unlink()
+        raise
+    return files_dir / name
+
+def move_to_quarantine(path, quarantine_dir, start_dir):
+    """Move a file below quarantine_dir, keeping its path relative to start_dir."""
+    target = quarantine_dir / path.relative_to(start_dir)
+    if target.exists():
+        raise FileExistsError(f"{target} already exists")
+    target.parent.mkdir(parents=True, exist_ok=True)
+    shutil.move(str(path), target)
+    return target
 
 def check_artwork(file_path):
     """Check if a file contains artwork."""
@@ -16,12 +158,115 @@ def check_artwork(file_path):
     ], capture_output=True, text=True)
     return 'video' in result.stdout
 
-def convert_flac_to_aiff(input_path, delete_original=True):
-    """Convert a FLAC file to AIFF format."""
+def copy_xattrs(src, dst):
+    """Copy extended attributes from src to dst, where the platform supports them."""
+    if not hasattr(os, 'listxattr'):
+        return
+    for name in os.listxattr(src):
+        try:
+            os.setxattr(dst, name, os.getxattr(src, name))
+        except OSError as e:
+            print(f"Warning: Could not copy attribute {name} to {dst}: {str(e)}")
+
+def preserve_metadata(src, dst, times=True, attrs=False):
+    """Carry file times, and optionally permissions and xattrs, over from src to dst."""
+    if attrs:
+        shutil.copymode(src, dst)
+        copy_xattrs(src, dst)
+    if times:
+        st = os.stat(src)
+        os.utime(dst, ns=(st.st_atime_ns, st.st_mtime_ns))
+
+def parse_duration(text):
+    """Seconds in a length like '30s', '1m' or '90'."""
+    units = {'s': 1, 'm': 60}
+    text = text.strip().lower()
+    try:
+        if text[-1:] in units:
+            seconds = float(text[:-1]) * units[text[-1]]
+        else:
+            seconds = float(text)
+    except ValueError:
+        raise argparse.ArgumentTypeError(f"invalid length: '{text}'")
+    if seconds <= 0:
+        raise argparse.ArgumentTypeError(f"length must be positive: '{text}'")
+    return seconds
+
+def audio_duration(file_path):
+    """Duration of a file in seconds, or None if ffprobe can't tell."""
+    result = subprocess.run([
+        'ffprobe',
+        '-v', 'error',
+        '-show_entries', 'format=duration',
+        '-of', 'default=noprint_wrappers=1:nokey=1',
+        str(file_path)
+    ], capture_output=True, text=True)
+    try:
+        return float(result.stdout.strip())
+    except ValueError:
+        return None
+
+def write_preview(aiff_path, preview_dir, start_dir, length, codec='mp3'):
+    """Write a short loudness-normalized preview of a converted file below preview_dir."""
+    encoder, suffix = PREVIEW_CODECS[codec]
+    preview_path = (preview_dir / aiff_path.relative_to(start_dir)).with_suffix(suffix)
+    if preview_path.exists():
+        print(f"Skipping preview {preview_path} - already exists")
+        return
+
+    # Start a third of the way in, where tracks have usually got going
+    duration = audio_duration(aiff_path)
+    start = 0
+    if duration and duration > length:
+        start = min(duration / 3, duration - length)
+
+    preview_path.parent.mkdir(parents=True, exist_ok=True)
+    try:
+        subprocess.run([
+            'ffmpeg',
+            '-ss', f"{start:.2f}",
+            '-t', f"{length:.2f}",
+            '-i', str(aiff_path),
+            '-map', '0:a',
+            '-map_metadata', '0',
+            '-af', f"loudnorm=I={PREVIEW_LOUDNESS}:TP=-1.5:LRA=11",
+            '-ar', '44100',        # loudnorm upsamples to 192 kHz
+            '-c:a', encoder,
+            '-b:a', '192k',
+            str(preview_path)
+        ], check=True, capture_output=True)
+        print(f"Wrote preview: {preview_path}")
+    except subprocess.CalledProcessError as e:
+        print(f"Warning: Could not write preview for {aiff_path}: {e.stderr.decode()}")
+
+def remove_original(input_path, purge=False, quarantine_dir=None, start_dir=None):
+    """Get a converted FLAC file out of the way: trash, quarantine or delete it."""
+    try:
+        if purge:
+            input_path.unlink()
+            print(f"Deleted original file: {input_path}")
+        elif quarantine_dir:
+            target = move_to_quarantine(input_path, quarantine_dir, start_dir)
+            print(f"Moved original file to {target}")
+        else:
+            move_to_trash(input_path)
+            print(f"Moved original file to trash: {input_path}")
+    except Exception as e:
+        print(f"Warning: Could not remove original file {input_path}: {str(e)}")
+
+def convert_flac_to_aiff(input_path, remove=None, preserve_times=True, preserve_attrs=False,
+                         preview=None):
+    """Convert a FLAC file to AIFF format.
+
+    Once converted, preview is called with the AIFF and remove with the original.
+    Files converted before still get a preview.
+    """
     output_path = input_path.with_suffix('.aif')
 
     if output_path.exists():
         print(f"Skipping {input_path} - {output_path} already exists")
+        if preview:
+            preview(output_path)
         return True
 
     print(f"Converting {input_path} to {output_path}")
@@ -49,12 +294,16 @@ def convert_flac_to_aiff(input_path, delete_original=True):
         else:
             print(f"Successfully converted {input_path}")
 
-        if delete_original:
-            try:
-                input_path.unlink()
-                print(f"Deleted original file: {input_path}")
-            except Exception as e:
-                print(f"Warning: Could not delete original file {input_path}: {str(e)}")
+        try:
+            preserve_metadata(input_path, output_path, preserve_times, preserve_attrs)
+        except OSError as e:
+            print(f"Warning: Could not preserve file metadata for {output_path}: {str(e)}")
+
+        if preview:
+            preview(output_path)
+
+        if remove:
+            remove(input_path)
 
         return True
 
@@ -69,8 +318,27 @@ def convert_flac_to_aiff(input_path, delete_original=True):
 def main():
     parser = argparse.ArgumentParser(description='Convert FLAC files to AIFF format recursively.')
     parser.add_argument('folder_path', help='Path to the folder containing FLAC files')
-    parser.add_argument('--keep-original', action='store_true',
-                       help='Keep original FLAC files after conversion (default: delete)')
+    removal = parser.add_mutually_exclusive_group()
+    removal.add_argument('--keep-original', action='store_true',
+                        help='Keep original FLAC files after conversion (default: move them to the trash)')
+    removal.add_argument('--quarantine', metavar='DIR',
+                        help='Move original FLAC files to DIR, keeping their folder structure, instead of the trash')
+    removal.add_argument('--purge', action='store_true',
+                        help='Delete original FLAC files instead of moving them to the trash')
+    parser.add_argument('--no-preserve-times', action='store_true',
+                       help="Don't copy access and modification times from the FLAC files")
+    parser.add_argument('--preserve-attrs', action='store_true',
+                       help='Also copy permissions and extended attributes from the FLAC files')
+    parser.add_argument('--exclude', action='append', default=[], metavar='GLOB',
+                       help="Skip files matching GLOB, relative to the folder (e.g. '**/live/**'); "
+                            f"can be repeated, and patterns in {IGNORE_FILE} are added")
+    parser.add_argument('--preview-dir', metavar='DIR',
+                       help='Also write short loudness-normalized previews of converted files to DIR, '
+                            'keeping their folder structure')
+    parser.add_argument('--preview-length', type=parse_duration, default=30, metavar='LENGTH',
+                       help="Length of each preview, e.g. '30s' or '1m' (default: 30s)")
+    parser.add_argument('--preview-format', choices=sorted(PREVIEW_CODECS), default='mp3',
+                       help='Format of the previews (default: mp3)')
     args = parser.parse_args()
 
     start_dir = Path(args.folder_path).resolve()
@@ -81,26 +349,43 @@ def main():
         print(f"Error: '{start_dir}' is not a directory.")
         return
 
+    remove = None
+    if not args.keep_original:
+        quarantine_dir = Path(args.quarantine).expanduser().resolve() if args.quarantine else None
+        # The next run would find the quarantined originals and convert them again
+        if quarantine_dir and quarantine_dir.is_relative_to(start_dir):
+            print(f"Error: The quarantine directory '{quarantine_dir}' is inside '{start_dir}'.")
+            return
+        remove = lambda path: remove_original(path, args.purge, quarantine_dir, start_dir)
+
+    preview = None
+    if args.preview_dir:
+        preview_dir = Path(args.preview_dir).expanduser().resolve()
+        preview = lambda path: write_preview(path, preview_dir, start_dir,
+                                             args.preview_length, args.preview_format)
 
     converted = 0
-    deleted = 0
+    removed = 0
     errors = 0
 
-    for flac_file in start_dir.rglob('*.flac'):
+    for flac_file in collect_flac_files(start_dir, args.exclude):
         try:
-            success = convert_flac_to_aiff(flac_file, not args.keep_original)
+            success = convert_flac_to_aiff(flac_file, remove,
+                                           not args.no_preserve_times, args.preserve_attrs,
+                                           preview)
             if success:
                 converted += 1
-                if not args.keep_original and not flac_file.exists():
-                    deleted += 1
+                if remove and not flac_file.exists():
+                    removed += 1
         except Exception as e:
             print(f"Failed to process {flac_file}: {str(e)}")
             errors += 1
 
     print('\nConversion Complete!')
     print(f"Successfully converted: {converted} files")
-    if not args.keep_original:
-        print(f"Original files deleted: {deleted} files")
+    if remove:
+        action = 'deleted' if args.purge else 'quarantined' if args.quarantine else 'moved to trash'
+        print(f"Original files {action}: {removed} files")
     if errors > 0:
         print(f"Errors encountered: {errors} files")
 
@@ -0,0 +1,179 @@
+use crate::progress;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+
+/// How long a query that found nothing is remembered, so new releases show up soon
+const MISS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// On-disk cache of fetcher responses, one JSON file per source and query
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    /// Only answer from the cache, expired entries included
+    offline: bool,
+}
+
+/// Error for queries that aren't in the cache in offline mode
+#[derive(Debug)]
+pub struct NotCached;
+
+impl std::fmt::Display for NotCached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("not in the cache")
+    }
+}
+
+impl std::error::Error for NotCached {}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry<T> {
+    key: String,
+    fetched_at: u64,
+    /// Nothing was found, which expires after [`MISS_TTL`]
+    #[serde(default)]
+    miss: bool,
+    value: T,
+}
+
+impl Cache {
+    /// Open the cache under `$XDG_CACHE_HOME/hakunadata` (or `~/.cache/hakunadata`)
+    pub fn new(ttl: Duration, offline: bool) -> Result<Self> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .context("Neither XDG_CACHE_HOME nor HOME is set")?;
+
+        Ok(Self {
+            dir: base.join("hakunadata"),
+            ttl,
+            offline,
+        })
+    }
+
+    /// Return the cached value for a query if it exists and hasn't expired
+    pub fn get<T: DeserializeOwned>(&self, source: &str, parts: &[&str]) -> Option<T> {
+        let key = normalize_key(parts);
+        let content = fs::read_to_string(self.entry_path(source, &key)).ok()?;
+        let entry: CacheEntry<T> = serde_json::from_str(&content).ok()?;
+
+        // Guard against (unlikely) hash collisions
+        if entry.key != key {
+            return None;
+        }
+
+        let ttl = if entry.miss {
+            self.ttl.min(MISS_TTL)
+        } else {
+            self.ttl
+        };
+        let age = now().saturating_sub(entry.fetched_at);
+        (self.offline || age < ttl.as_secs()).then_some(entry.value)
+    }
+
+    /// Store a value for a query, replacing any previous entry
+    pub fn put<T: Serialize>(
+        &self,
+        source: &str,
+        parts: &[&str],
+        value: &T,
+        miss: bool,
+    ) -> Result<()> {
+        let key = normalize_key(parts);
+        let path = self.entry_path(source, &key);
+
+        let parent = path.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+
+        let entry = CacheEntry {
+            key,
+            fetched_at: now(),
+            miss,
+            value,
+        };
+
+        // Write to a temporary file first so readers never see a partial entry,
+        // uniquely named as concurrent albums may store the same query
+        let mut tmp = NamedTempFile::new_in(parent)
+            .with_context(|| format!("Failed to create cache entry in {}", parent.display()))?;
+        tmp.write_all(&serde_json::to_vec(&entry)?)
+            .context("Failed to write cache entry")?;
+        tmp.persist(&path)
+            .with_context(|| format!("Failed to write cache entry: {}", path.display()))?;
+
+        Ok(())
+    }
+
+    fn entry_path(&self, source: &str, key: &str) -> PathBuf {
+        self.dir
+            .join(source)
+            .join(format!("{:016x}.json", fnv1a(key)))
+    }
+}
+
+/// Look up a query in the cache, falling back to `fetch` and caching the result
+///
+/// Results that `found` rejects are cached as misses, which expire sooner. In
+/// offline mode, queries missing from the cache fail with [`NotCached`].
+pub async fn cached<T, F>(
+    cache: Option<&Cache>,
+    source: &str,
+    parts: &[&str],
+    found: fn(&T) -> bool,
+    fetch: F,
+) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+    F: Future<Output = Result<T>>,
+{
+    let Some(cache) = cache else {
+        return fetch.await;
+    };
+
+    if let Some(value) = cache.get(source, parts) {
+        return Ok(value);
+    }
+    if cache.offline {
+        return Err(NotCached.into());
+    }
+
+    let value = fetch.await?;
+    if let Err(e) = cache.put(source, parts, &value, !found(&value)) {
+        progress::eprintln(format!("Warning: {e:?}"));
+    }
+
+    Ok(value)
+}
+
+/// Build a cache key that ignores case and whitespace differences
+fn normalize_key(parts: &[&str]) -> String {
+    parts
+        .iter()
+        .map(|part| {
+            part.split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        })
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+/// 64-bit FNV-1a, stable across runs and Rust versions unlike `DefaultHasher`
+pub fn fnv1a(input: &str) -> u64 {
+    input.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::PathBuf;
+use toml_edit::{DocumentMut, Item};
+
+/// Path of a file in `$XDG_CONFIG_HOME/hakunadata` (or `~/.config/hakunadata`)
+pub fn config_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|base| base.join("hakunadata").join(name))
+}
+
+/// Path of a file in `$XDG_DATA_HOME/hakunadata` (or `~/.local/share/hakunadata`)
+pub fn data_path(name: &str) -> Result<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })
+        .context("Neither XDG_DATA_HOME nor HOME is set")?;
+
+    Ok(base.join("hakunadata").join(name))
+}
+
+/// Parse a TOML file from the config directory, if it exists
+pub fn load(name: &str) -> Result<Option<(PathBuf, DocumentMut)>> {
+    let Some(path) = config_path(name).filter(|path| path.exists()) else {
+        return Ok(None);
+    };
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let doc = content
+        .parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    Ok(Some((path, doc)))
+}
+
+/// Parse a string item as one of the values accepted by the matching CLI flag
+pub fn parse_enum<T: ValueEnum>(key: &str, item: &Item) -> Result<T> {
+    let value = item
+        .as_str()
+        .with_context(|| format!("'{key}' must be a string"))?;
+    T::from_str(value, true).map_err(|_| anyhow::anyhow!("Invalid value for '{key}': {value}"))
+}
@@ -1,7 +1,39 @@
+use crate::cache::Cache;
+use crate::fetchers::bandcamp::BandcampClient;
+use crate::fetchers::coverart::CoverArtClient;
 use crate::fetchers::discogs::DiscogsClient;
+use crate::fetchers::lastfm::LastFmClient;
 use crate::fetchers::musicbrainz::MusicBrainzClient;
+use crate::fetchers::spotify::SpotifyClient;
+use crate::journal::Journal;
+use crate::metadata::filter::TagFilter;
+use crate::metadata::genres::GenreMap;
+use crate::metadata::matching::Preference;
+use crate::path_pattern::PathPattern;
+use crate::tagging::WritePolicy;
 
 pub struct AppContext {
     pub mb_client: Option<MusicBrainzClient>,
     pub discogs_client: Option<DiscogsClient>,
+    pub lastfm_client: Option<LastFmClient>,
+    pub spotify_client: Option<SpotifyClient>,
+    pub bandcamp_client: Option<BandcampClient>,
+    pub art_client: Option<CoverArtClient>,
+    pub cache: Option<Cache>,
+    pub genre_map: GenreMap,
+    pub filter: TagFilter,
+    pub policy: WritePolicy,
+    pub path_pattern: Option<PathPattern>,
+    /// Candidates scoring below this are treated as no match
+    pub min_score: f64,
+    /// Look up every file by its title instead of its album
+    pub by_track: bool,
+    /// Treat every file as a compilation track or none, instead of detecting them
+    pub compilation: Option<bool>,
+    /// Prefer genres, styles and year of the Discogs master release
+    pub discogs_master: bool,
+    /// Country and format of the pressing to pick among equal matches
+    pub preference: Preference,
+    /// Records original tags when writing, for `hakunadata undo`
+    pub journal: Option<Journal>,
 }
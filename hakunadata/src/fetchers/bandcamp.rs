@@ -0,0 +1,243 @@
+use crate::fetchers;
+use crate::fetchers::rate_limit::RateLimiter;
+use crate::metadata::matching;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Tags, label and year of the best matching Bandcamp album
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BandcampResult {
+    /// Matched release as "Artist - Album"
+    pub matched: String,
+    pub score: f64,
+    pub genres: Vec<String>,
+    pub label: Option<String>,
+    pub year: Option<String>,
+}
+
+/// Bandcamp has no public API, so this goes through the search box's
+/// endpoint and the album page's JSON-LD
+pub struct BandcampClient {
+    client: reqwest::Client,
+    limiter: RateLimiter,
+}
+
+impl BandcampClient {
+    pub fn new(proxy: Option<&str>) -> Result<Self> {
+        let client = fetchers::http_client(proxy)?;
+
+        Ok(Self {
+            client,
+            // Scraping, so keep it gentle
+            limiter: RateLimiter::new(Duration::from_secs(1)),
+        })
+    }
+
+    pub async fn fetch_metadata(
+        &self,
+        artist: &str,
+        release: &str,
+    ) -> Result<Option<BandcampResult>> {
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .post("https://bandcamp.com/api/bcsearch_public_api/1/autocomplete_elastic")
+            .json(&serde_json::json!({
+                "search_text": format!("{artist} {release}"),
+                "search_filter": "a",
+                "full_page": false,
+                "fan_id": null,
+            }))
+            .send()
+            .await
+            .context("Failed to send Bandcamp request")?;
+
+        fetchers::check_rate_limit("bandcamp", response.status())?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let search: BandcampSearchResponse = response.json().await?;
+        let scored = search
+            .auto
+            .results
+            .into_iter()
+            .filter(|item| item.kind == "a" && !item.item_url_path.is_empty())
+            .map(|item| {
+                let score = matching::score(artist, release, &item.band_name, &item.name);
+                (item, score)
+            });
+
+        let Some((item, score)) = matching::best(scored) else {
+            return Ok(None);
+        };
+        let matched = format!("{} - {}", item.band_name, item.name);
+
+        let Some(album) = self.fetch_album(&item.item_url_path).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(BandcampResult {
+            matched,
+            score,
+            genres: album.tags(),
+            label: album.label(),
+            year: album.year(),
+        }))
+    }
+
+    /// Structured data embedded in the album page
+    async fn fetch_album(&self, url: &str) -> Result<Option<BandcampAlbum>> {
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("Failed to send Bandcamp request")?;
+
+        fetchers::check_rate_limit("bandcamp", response.status())?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let html = response.text().await?;
+        let Some(json) = json_ld(&html) else {
+            return Ok(None);
+        };
+
+        Ok(serde_json::from_str(json).ok())
+    }
+}
+
+/// Contents of the page's `application/ld+json` script
+fn json_ld(html: &str) -> Option<&str> {
+    let start = html.find("application/ld+json")?;
+    let rest = &html[start..];
+    let rest = &rest[rest.find('>')? + 1..];
+    Some(rest[..rest.find("</script>")?].trim())
+}
+
+// --- Serde Structs ---
+
+#[derive(Deserialize, Debug)]
+struct BandcampSearchResponse {
+    auto: BandcampResults,
+}
+
+#[derive(Deserialize, Debug)]
+struct BandcampResults {
+    #[serde(default)]
+    results: Vec<BandcampSearchItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BandcampSearchItem {
+    /// "a" for albums, "b" for artists and labels, "t" for tracks
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    band_name: String,
+    #[serde(default)]
+    item_url_path: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BandcampAlbum {
+    by_artist: Option<BandcampEntity>,
+    /// The account selling the album, the artist's own or a label's
+    publisher: Option<BandcampEntity>,
+    #[serde(default)]
+    album_release: Vec<BandcampRelease>,
+    /// The album's tags
+    keywords: Option<Keywords>,
+    /// e.g. "18 Jul 2007 00:00:00 GMT"
+    date_published: Option<String>,
+}
+
+impl BandcampAlbum {
+    /// Tags without the artist's location, which Bandcamp lists among them
+    fn tags(&self) -> Vec<String> {
+        let location = self
+            .by_artist
+            .as_ref()
+            .and_then(|artist| artist.founding_location.as_ref())
+            .and_then(|location| location.name.split(',').next())
+            .map(str::trim);
+
+        let keywords = match &self.keywords {
+            Some(Keywords::List(tags)) => tags.clone(),
+            Some(Keywords::Joined(tags)) => tags.split(',').map(str::to_string).collect(),
+            None => vec![],
+        };
+
+        keywords
+            .into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| {
+                !tag.is_empty()
+                    && location.is_none_or(|location| !tag.eq_ignore_ascii_case(location))
+            })
+            .collect()
+    }
+
+    /// Label credited on the release, or the account selling it if that isn't the artist's
+    fn label(&self) -> Option<String> {
+        let credited = self
+            .album_release
+            .iter()
+            .find_map(|release| release.record_label.as_ref())
+            .map(|label| label.name.clone());
+
+        credited
+            .or_else(|| {
+                let publisher = self.publisher.as_ref()?;
+                let artist = self.by_artist.as_ref()?;
+                let own_account = publisher
+                    .name
+                    .trim()
+                    .eq_ignore_ascii_case(artist.name.trim());
+                (!own_account).then(|| publisher.name.clone())
+            })
+            .filter(|label| !label.is_empty())
+    }
+
+    fn year(&self) -> Option<String> {
+        self.date_published
+            .as_deref()?
+            .split_whitespace()
+            .find(|part| part.len() == 4 && part.chars().all(|c| c.is_ascii_digit()))
+            .map(str::to_string)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BandcampEntity {
+    #[serde(default)]
+    name: String,
+    founding_location: Option<BandcampLocation>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BandcampLocation {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BandcampRelease {
+    record_label: Option<BandcampEntity>,
+}
+
+/// Usually a list, though schema.org also allows a comma-separated string
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Keywords {
+    List(Vec<String>),
+    Joined(String),
+}
@@ -0,0 +1,80 @@
+use crate::fetchers;
+use anyhow::{Context, Result};
+use lofty::picture::{Picture, PictureType};
+
+/// Thumbnail sizes served by the Cover Art Archive
+const THUMBNAIL_SIZES: &[u32] = &[250, 500, 1200];
+
+/// Downloads front covers from the Cover Art Archive or Discogs
+pub struct CoverArtClient {
+    client: reqwest::Client,
+    max_size: u32,
+}
+
+impl CoverArtClient {
+    pub fn new(max_size: u32, proxy: Option<&str>) -> Result<Self> {
+        let client = fetchers::http_client(proxy)?;
+
+        Ok(Self { client, max_size })
+    }
+
+    /// Candidate URLs for the front cover, best first
+    pub fn cover_urls(
+        &self,
+        release_id: Option<&str>,
+        release_group_id: Option<&str>,
+        discogs_image: Option<&str>,
+    ) -> Vec<String> {
+        // Largest thumbnail that fits, or the smallest one if max_size is below all of them
+        let size = THUMBNAIL_SIZES
+            .iter()
+            .copied()
+            .filter(|size| *size <= self.max_size)
+            .max()
+            .unwrap_or(THUMBNAIL_SIZES[0]);
+
+        let mut urls = Vec::new();
+        if let Some(id) = release_id {
+            urls.push(format!(
+                "https://coverartarchive.org/release/{id}/front-{size}"
+            ));
+        }
+        if let Some(id) = release_group_id {
+            urls.push(format!(
+                "https://coverartarchive.org/release-group/{id}/front-{size}"
+            ));
+        }
+        // Discogs returns a placeholder when a release has no images
+        if let Some(url) = discogs_image.filter(|url| !url.ends_with("spacer.gif")) {
+            urls.push(url.to_string());
+        }
+
+        urls
+    }
+
+    /// Download the first candidate that resolves to an image
+    pub async fn fetch(&self, urls: &[String]) -> Result<Option<(String, Picture)>> {
+        for url in urls {
+            let response = self
+                .client
+                .get(url)
+                .send()
+                .await
+                .context("Failed to send cover art request")?;
+
+            if !response.status().is_success() {
+                continue;
+            }
+
+            let data = response.bytes().await?;
+            let Ok(mut picture) = Picture::from_reader(&mut data.as_ref()) else {
+                continue;
+            };
+            picture.set_pic_type(PictureType::CoverFront);
+
+            return Ok(Some((url.clone(), picture)));
+        }
+
+        Ok(None)
+    }
+}
@@ -1,41 +1,56 @@
+use crate::fetchers;
+use crate::fetchers::rate_limit::RateLimiter;
+use crate::metadata::matching::{self, Preference};
+use crate::progress;
 use anyhow::{Context, Result};
-use reqwest::header;
-use serde::Deserialize;
-
-const USER_AGENT: &str = "hakunadata/0.1.0 ( miro@haravara.org )";
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub struct DiscogsClient {
     client: reqwest::Client,
+    limiter: RateLimiter,
     token: Option<String>,
 }
 
 impl DiscogsClient {
-    pub fn new() -> Result<Self> {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_static(USER_AGENT),
-        );
-
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
+    pub fn new(proxy: Option<&str>) -> Result<Self> {
+        let client = fetchers::http_client(proxy)?;
 
         let token = std::env::var("DISCOGS_TOKEN").ok();
 
-        Ok(Self { client, token })
+        // 60 requests per minute with a token, 25 without
+        let interval = if token.is_some() {
+            Duration::from_secs(1)
+        } else {
+            Duration::from_millis(2400)
+        };
+
+        Ok(Self {
+            client,
+            token,
+            limiter: RateLimiter::new(interval),
+        })
     }
 
     pub async fn fetch_metadata(
         &self,
         artist: &str,
         release: &str,
+        preference: &Preference,
     ) -> Result<Option<DiscogsResult>> {
         let url = "https://api.discogs.com/database/search";
+        // Discogs credits compilations to "Various"
+        let query_artist = if matching::is_various(artist) {
+            "Various"
+        } else {
+            artist
+        };
         let mut query = vec![
             ("type", "release"),
-            ("artist", artist),
+            ("artist", query_artist),
             ("release_title", release),
+            // A preferred pressing may well be further down than the first few
+            ("per_page", if preference.is_empty() { "10" } else { "50" }),
         ];
 
         let token_string;
@@ -44,6 +59,7 @@ impl DiscogsClient {
             query.push(("token", &token_string));
         }
 
+        self.limiter.wait().await;
         let response = self
             .client
             .get(url)
@@ -52,12 +68,13 @@ impl DiscogsClient {
             .await
             .context("Failed to send Discogs request")?;
 
+        fetchers::check_rate_limit("discogs", response.status())?;
         if !response.status().is_success() {
             if response.status() == reqwest::StatusCode::UNAUTHORIZED {
                 use std::sync::atomic::{AtomicBool, Ordering};
                 static WARNED: AtomicBool = AtomicBool::new(false);
                 if !WARNED.swap(true, Ordering::Relaxed) {
-                    eprintln!("DISCOGS_TOKEN is required");
+                    progress::eprintln("DISCOGS_TOKEN is required");
                 }
             }
             return Ok(None);
@@ -65,7 +82,49 @@ impl DiscogsClient {
 
         let search_result: DiscogsSearchResponse = response.json().await?;
 
-        Ok(search_result.results.into_iter().next())
+        // Titles are "Artist - Album", with numbered artists like "Djrum (2)"
+        let scored = search_result.results.into_iter().map(|result| {
+            let score = result.title.as_deref().map_or(0.0, |title| {
+                let (candidate_artist, candidate_album) =
+                    title.split_once(" - ").unwrap_or(("", title));
+                matching::score(artist, release, candidate_artist, candidate_album)
+            });
+            let bonus = preference.bonus(
+                result.country.as_deref(),
+                result.format.iter().flatten().map(String::as_str),
+            );
+            (result, score, bonus)
+        });
+
+        Ok(matching::best_preferred(scored).map(|(mut result, score)| {
+            result.score = score;
+            result
+        }))
+    }
+
+    /// Look up a master release, which carries the genres, styles and year shared by all pressings
+    pub async fn fetch_master(&self, id: u64) -> Result<Option<DiscogsMaster>> {
+        let url = format!("https://api.discogs.com/masters/{id}");
+        let mut query = vec![];
+        if let Some(t) = &self.token {
+            query.push(("token", t.as_str()));
+        }
+
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get(&url)
+            .query(&query)
+            .send()
+            .await
+            .context("Failed to send Discogs request")?;
+
+        fetchers::check_rate_limit("discogs", response.status())?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        Ok(Some(response.json().await?))
     }
 }
 
@@ -74,9 +133,30 @@ pub struct DiscogsSearchResponse {
     pub results: Vec<DiscogsResult>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DiscogsResult {
+    pub title: Option<String>,
+    /// Similarity to the query, filled in after the search
+    #[serde(default)]
+    pub score: f64,
     pub genre: Option<Vec<String>>,
     pub style: Option<Vec<String>>,
     pub label: Option<Vec<String>>,
+    pub cover_image: Option<String>,
+    pub year: Option<String>,
+    pub country: Option<String>,
+    /// Format and its descriptions, e.g. `["Vinyl", "LP", "Album"]`
+    pub format: Option<Vec<String>>,
+    pub catno: Option<String>,
+    /// 0 for releases that don't belong to a master
+    #[serde(default)]
+    pub master_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiscogsMaster {
+    pub genres: Option<Vec<String>>,
+    pub styles: Option<Vec<String>>,
+    /// 0 when unknown
+    pub year: Option<u32>,
 }
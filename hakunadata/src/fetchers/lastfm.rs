@@ -0,0 +1,179 @@
+use crate::fetchers::{self, GenreResult};
+use crate::fetchers::rate_limit::RateLimiter;
+use crate::metadata::matching;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::time::Duration;
+
+pub struct LastFmClient {
+    client: reqwest::Client,
+    limiter: RateLimiter,
+    api_key: String,
+}
+
+impl LastFmClient {
+    pub fn new(proxy: Option<&str>) -> Result<Self> {
+        let client = fetchers::http_client(proxy)?;
+
+        let api_key = std::env::var("LASTFM_API_KEY").context("LASTFM_API_KEY is required")?;
+
+        Ok(Self {
+            client,
+            api_key,
+            // Last.fm asks for no more than 5 requests per second
+            limiter: RateLimiter::new(Duration::from_millis(200)),
+        })
+    }
+
+    pub async fn fetch_genres(&self, artist: &str, release: &str) -> Result<Option<GenreResult>> {
+        let url = "https://ws.audioscrobbler.com/2.0/";
+
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get(url)
+            .query(&[
+                ("method", "album.getinfo"),
+                ("api_key", self.api_key.as_str()),
+                ("artist", artist),
+                ("album", release),
+                ("autocorrect", "1"),
+                ("format", "json"),
+            ])
+            .send()
+            .await
+            .context("Failed to send Last.fm request")?;
+
+        fetchers::check_rate_limit("lastfm", response.status())?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        // Unknown albums come back as `{"error": 6, "message": "..."}` without an `album` key
+        let info: LastFmAlbumInfoResponse = response.json().await?;
+
+        let Some(album) = info.album else {
+            return Ok(None);
+        };
+
+        // Autocorrect may have resolved the query to something else entirely
+        let score = matching::score(artist, release, &album.artist, &album.name);
+        let matched = format!("{} - {}", album.artist, album.name);
+
+        // Albums without tags have `"tags": ""` instead of an object
+        let Some(Ok(tags)) = album.tags.map(serde_json::from_value::<LastFmTagList>) else {
+            return Ok(None);
+        };
+
+        Ok(Some(GenreResult {
+            matched,
+            score,
+            genres: tag_names(tags),
+        }))
+    }
+
+    /// Top tags of a single track
+    pub async fn fetch_track_genres(
+        &self,
+        artist: &str,
+        title: &str,
+    ) -> Result<Option<GenreResult>> {
+        let url = "https://ws.audioscrobbler.com/2.0/";
+
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get(url)
+            .query(&[
+                ("method", "track.getinfo"),
+                ("api_key", self.api_key.as_str()),
+                ("artist", artist),
+                ("track", title),
+                ("autocorrect", "1"),
+                ("format", "json"),
+            ])
+            .send()
+            .await
+            .context("Failed to send Last.fm request")?;
+
+        fetchers::check_rate_limit("lastfm", response.status())?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let info: LastFmTrackInfoResponse = response.json().await?;
+
+        let Some(track) = info.track else {
+            return Ok(None);
+        };
+
+        let score = matching::score(artist, title, &track.artist.name, &track.name);
+        let matched = format!("{} - {}", track.artist.name, track.name);
+
+        let Some(Ok(tags)) = track.toptags.map(serde_json::from_value::<LastFmTagList>) else {
+            return Ok(None);
+        };
+
+        Ok(Some(GenreResult {
+            matched,
+            score,
+            genres: tag_names(tags),
+        }))
+    }
+}
+
+fn tag_names(tags: LastFmTagList) -> Vec<String> {
+    match tags.tag {
+        OneOrMany::One(t) => vec![t.name],
+        OneOrMany::Many(tags) => tags.into_iter().map(|t| t.name).collect(),
+    }
+}
+
+// --- Serde Structs ---
+
+#[derive(Deserialize, Debug)]
+struct LastFmAlbumInfoResponse {
+    album: Option<LastFmAlbum>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastFmAlbum {
+    name: String,
+    artist: String,
+    tags: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastFmTrackInfoResponse {
+    track: Option<LastFmTrack>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastFmTrack {
+    name: String,
+    artist: LastFmArtistRef,
+    toptags: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastFmArtistRef {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LastFmTagList {
+    tag: OneOrMany<LastFmTag>,
+}
+
+/// Single-element lists are serialized as a bare object
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+#[derive(Deserialize, Debug)]
+struct LastFmTag {
+    name: String,
+}
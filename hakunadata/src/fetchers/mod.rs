@@ -1,2 +1,78 @@
+pub mod bandcamp;
+pub mod coverart;
 pub mod discogs;
+pub mod lastfm;
 pub mod musicbrainz;
+pub mod rate_limit;
+pub mod spotify;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+
+const USER_AGENT: &str = "hakunadata/0.1.0 ( miro@haravara.org )";
+
+/// Metadata sources selectable with `--sources`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Discogs,
+    Musicbrainz,
+    Lastfm,
+    Spotify,
+    Bandcamp,
+}
+
+/// Genres of the best match from a genre-only source
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GenreResult {
+    /// Matched release as "Artist - Album"
+    pub matched: String,
+    pub score: f64,
+    pub genres: Vec<String>,
+}
+
+/// HTTP client for a source, going through `proxy` if given
+///
+/// Without one, reqwest picks up `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY`
+/// and `NO_PROXY` from the environment.
+pub fn http_client(proxy: Option<&str>) -> Result<reqwest::Client> {
+    let mut headers = header::HeaderMap::new();
+    headers.insert(
+        header::USER_AGENT,
+        header::HeaderValue::from_static(USER_AGENT),
+    );
+
+    let mut builder = reqwest::Client::builder().default_headers(headers);
+    if let Some(proxy) = proxy {
+        let proxy =
+            reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy: {proxy}"))?;
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Error for a source refusing requests because too many were sent
+#[derive(Debug)]
+pub struct RateLimited(pub &'static str);
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} rate limit exceeded", self.0)
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Fail with [`RateLimited`] if `status` says the source is throttling us
+///
+/// MusicBrainz answers with 503 rather than 429 when requests come too fast.
+pub fn check_rate_limit(source: &'static str, status: reqwest::StatusCode) -> Result<()> {
+    let throttled = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || (source == "musicbrainz" && status == reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    if throttled {
+        return Err(RateLimited(source).into());
+    }
+    Ok(())
+}
@@ -1,52 +1,64 @@
+use crate::fetchers;
+use crate::fetchers::rate_limit::RateLimiter;
+use crate::metadata::matching::{self, Preference};
 use anyhow::{Context, Result};
-use reqwest::header;
-use serde::Deserialize;
-
-const USER_AGENT: &str = "hakunadata/0.1.0 ( miro@haravara.org )";
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub struct MusicBrainzClient {
     client: reqwest::Client,
+    limiter: RateLimiter,
 }
 
 impl MusicBrainzClient {
-    pub fn new() -> Result<Self> {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_static(USER_AGENT),
-        );
-
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
+    pub fn new(proxy: Option<&str>) -> Result<Self> {
+        let client = fetchers::http_client(proxy)?;
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            // MusicBrainz allows one request per second
+            limiter: RateLimiter::new(Duration::from_secs(1)),
+        })
     }
 
-    pub async fn fetch_genres(&self, artist: &str, release: &str) -> Result<Vec<String>> {
+    pub async fn fetch_metadata(
+        &self,
+        artist: &str,
+        release: &str,
+        preference: &Preference,
+    ) -> Result<Option<MbResult>> {
         // First search for the release group to get a broader set of tags, or specific release.
         // Let's try searching for "release" first as it is more specific, but release-group often has the tags.
         // Actually, searching for release-group is usually better for genres as they adhere to the abstract album.
 
-        let query = format!("artist:\"{artist}\" AND release:\"{release}\"");
+        // Compilations are credited to the special "Various Artists" artist
+        let query_artist = if matching::is_various(artist) {
+            matching::VARIOUS_ARTISTS
+        } else {
+            artist
+        };
+        let query = format!("artist:\"{query_artist}\" AND release:\"{release}\"");
         let url = "https://musicbrainz.org/ws/2/release";
 
+        self.limiter.wait().await;
         let response = self
             .client
             .get(url)
             .query(&[
                 ("query", query.as_str()),
                 ("fmt", "json"),
-                ("limit", "1"), // Just take the best match
+                // Scored below to pick the best match, a preferred pressing needs more of them
+                ("limit", if preference.is_empty() { "5" } else { "25" }),
             ])
             .send()
             .await
             .context("Failed to send MusicBrainz request")?;
 
+        fetchers::check_rate_limit("musicbrainz", response.status())?;
         if !response.status().is_success() {
             // It's okay if we don't find it, but we should log it?
             // For now just return empty.
-            return Ok(vec![]);
+            return Ok(None);
         }
 
         let search_result: MbSearchResponse = response.json().await?;
@@ -61,18 +73,124 @@ impl MusicBrainzClient {
         // 2. Get ID.
         // 3. Lookup release-group with inc=tags.
 
-        if let Some(release_match) = search_result.releases.first() {
-            // If we have a release-group ID, use that.
-            if let Some(rg) = &release_match.release_group {
-                return self.lookup_release_group_tags(&rg.id).await;
-            }
+        let scored = search_result.releases.into_iter().map(|candidate| {
+            let candidate_artist = artist_name(&candidate.artist_credit);
+            let score = matching::score(artist, release, &candidate_artist, &candidate.title);
+            let bonus = preference.bonus(
+                candidate.country.as_deref(),
+                candidate
+                    .media
+                    .iter()
+                    .filter_map(|medium| medium.format.as_deref()),
+            );
+            (candidate, score, bonus)
+        });
+
+        let Some((release_match, score)) = matching::best_preferred(scored) else {
+            return Ok(None);
+        };
+        let matched = format!(
+            "{} - {}",
+            artist_name(&release_match.artist_credit),
+            release_match.title
+        );
+
+        // If we have a release-group ID, use that.
+        let (genres, first_release_date) = if let Some(rg) = &release_match.release_group {
+            self.lookup_release_group(&rg.id).await?
+        } else {
+            (vec![], None)
+        };
+
+        let catalog_number = release_match
+            .label_info
+            .into_iter()
+            .find_map(|info| info.catalog_number)
+            .filter(|catno| !catno.is_empty());
+
+        let artist_ids = release_match
+            .artist_credit
+            .into_iter()
+            .map(|credit| credit.artist.id)
+            .collect();
+
+        Ok(Some(MbResult {
+            matched,
+            score,
+            release_id: release_match.id,
+            release_group_id: release_match.release_group.map(|rg| rg.id),
+            artist_ids,
+            genres,
+            date: release_match.date.filter(|d| !d.is_empty()),
+            first_release_date,
+            country: release_match.country,
+            catalog_number,
+        }))
+    }
+
+    /// Search for a recording, for singles and compilation tracks whose album genres don't fit
+    pub async fn fetch_recording(
+        &self,
+        artist: &str,
+        title: &str,
+    ) -> Result<Option<MbRecordingResult>> {
+        let query = format!("artist:\"{artist}\" AND recording:\"{title}\"");
+        let url = "https://musicbrainz.org/ws/2/recording";
+
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get(url)
+            .query(&[("query", query.as_str()), ("fmt", "json"), ("limit", "5")])
+            .send()
+            .await
+            .context("Failed to send MusicBrainz request")?;
+
+        fetchers::check_rate_limit("musicbrainz", response.status())?;
+        if !response.status().is_success() {
+            return Ok(None);
         }
 
-        Ok(vec![])
+        let search_result: MbRecordingSearchResponse = response.json().await?;
+
+        let scored = search_result.recordings.into_iter().map(|candidate| {
+            let score = matching::score(
+                artist,
+                title,
+                &artist_name(&candidate.artist_credit),
+                &candidate.title,
+            );
+            (candidate, score)
+        });
+
+        let Some((recording, score)) = matching::best(scored) else {
+            return Ok(None);
+        };
+        let matched = format!(
+            "{} - {}",
+            artist_name(&recording.artist_credit),
+            recording.title
+        );
+
+        let genres = self.lookup_recording_genres(&recording.id).await?;
+
+        Ok(Some(MbRecordingResult {
+            matched,
+            score,
+            recording_id: recording.id,
+            artist_ids: recording
+                .artist_credit
+                .into_iter()
+                .map(|credit| credit.artist.id)
+                .collect(),
+            genres,
+            first_release_date: recording.first_release_date.filter(|d| !d.is_empty()),
+        }))
     }
 
-    async fn lookup_release_group_tags(&self, id: &str) -> Result<Vec<String>> {
-        let url = format!("https://musicbrainz.org/ws/2/release-group/{id}");
+    async fn lookup_recording_genres(&self, id: &str) -> Result<Vec<String>> {
+        let url = format!("https://musicbrainz.org/ws/2/recording/{id}");
+        self.limiter.wait().await;
         let response = self
             .client
             .get(&url)
@@ -80,10 +198,38 @@ impl MusicBrainzClient {
             .send()
             .await?;
 
+        fetchers::check_rate_limit("musicbrainz", response.status())?;
         if !response.status().is_success() {
             return Ok(vec![]);
         }
 
+        let recording: MbRecordingLookup = response.json().await?;
+
+        Ok(recording
+            .tags
+            .into_iter()
+            .flatten()
+            .chain(recording.genres.into_iter().flatten())
+            .map(|tag| tag.name)
+            .collect())
+    }
+
+    /// Look up the tags and first release date of a release group
+    async fn lookup_release_group(&self, id: &str) -> Result<(Vec<String>, Option<String>)> {
+        let url = format!("https://musicbrainz.org/ws/2/release-group/{id}");
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("fmt", "json"), ("inc", "tags+genres")])
+            .send()
+            .await?;
+
+        fetchers::check_rate_limit("musicbrainz", response.status())?;
+        if !response.status().is_success() {
+            return Ok((vec![], None));
+        }
+
         let rg: MbReleaseGroup = response.json().await?;
         let mut genres = Vec::new();
 
@@ -99,10 +245,41 @@ impl MusicBrainzClient {
             }
         }
 
-        Ok(genres)
+        let first_release_date = rg.first_release_date.filter(|d| !d.is_empty());
+
+        Ok((genres, first_release_date))
     }
 }
 
+/// Resolved release with the genres of its release group
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MbResult {
+    /// Matched release as "Artist - Album"
+    pub matched: String,
+    pub score: f64,
+    pub release_id: String,
+    pub release_group_id: Option<String>,
+    #[serde(default)]
+    pub artist_ids: Vec<String>,
+    pub genres: Vec<String>,
+    pub date: Option<String>,
+    pub first_release_date: Option<String>,
+    pub country: Option<String>,
+    pub catalog_number: Option<String>,
+}
+
+/// Resolved recording with its own genres
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MbRecordingResult {
+    /// Matched recording as "Artist - Title"
+    pub matched: String,
+    pub score: f64,
+    pub recording_id: String,
+    pub artist_ids: Vec<String>,
+    pub genres: Vec<String>,
+    pub first_release_date: Option<String>,
+}
+
 // --- Serde Structs ---
 
 #[derive(Deserialize, Debug)]
@@ -113,8 +290,72 @@ struct MbSearchResponse {
 
 #[derive(Deserialize, Debug)]
 struct MbRelease {
+    id: String,
+    title: String,
     #[serde(rename = "release-group")]
     release_group: Option<MbReleaseGroupRef>,
+    date: Option<String>,
+    country: Option<String>,
+    #[serde(rename = "label-info", default)]
+    label_info: Vec<MbLabelInfo>,
+    #[serde(default)]
+    media: Vec<MbMedium>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<MbArtistCredit>,
+}
+
+/// Artist credit as displayed, e.g. "Artist feat. Other"
+fn artist_name(credits: &[MbArtistCredit]) -> String {
+    credits
+        .iter()
+        .map(|credit| format!("{}{}", credit.name, credit.joinphrase))
+        .collect()
+}
+
+#[derive(Deserialize, Debug)]
+struct MbRecordingSearchResponse {
+    #[serde(default)]
+    recordings: Vec<MbRecording>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbRecording {
+    id: String,
+    title: String,
+    #[serde(rename = "first-release-date")]
+    first_release_date: Option<String>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<MbArtistCredit>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbRecordingLookup {
+    tags: Option<Vec<MbTag>>,
+    genres: Option<Vec<MbTag>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbArtistCredit {
+    name: String,
+    #[serde(default)]
+    joinphrase: String,
+    artist: MbArtistRef,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbArtistRef {
+    id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbMedium {
+    format: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MbLabelInfo {
+    #[serde(rename = "catalog-number")]
+    catalog_number: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -124,6 +365,8 @@ struct MbReleaseGroupRef {
 
 #[derive(Deserialize, Debug)]
 struct MbReleaseGroup {
+    #[serde(rename = "first-release-date")]
+    first_release_date: Option<String>,
     tags: Option<Vec<MbTag>>,
     genres: Option<Vec<MbTag>>,
 }
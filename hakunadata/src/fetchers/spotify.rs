@@ -0,0 +1,226 @@
+use crate::fetchers::{self, GenreResult};
+use crate::fetchers::rate_limit::RateLimiter;
+use crate::metadata::matching;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+pub struct SpotifyClient {
+    client: reqwest::Client,
+    limiter: RateLimiter,
+    client_id: String,
+    client_secret: String,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl SpotifyClient {
+    pub fn new(proxy: Option<&str>) -> Result<Self> {
+        let client = fetchers::http_client(proxy)?;
+
+        let client_id =
+            std::env::var("SPOTIFY_CLIENT_ID").context("SPOTIFY_CLIENT_ID is required")?;
+        let client_secret =
+            std::env::var("SPOTIFY_CLIENT_SECRET").context("SPOTIFY_CLIENT_SECRET is required")?;
+
+        Ok(Self {
+            client,
+            client_id,
+            client_secret,
+            token: Mutex::new(None),
+            // Spotify doesn't publish its limit, stay well below it
+            limiter: RateLimiter::new(Duration::from_millis(100)),
+        })
+    }
+
+    pub async fn fetch_genres(&self, artist: &str, release: &str) -> Result<Option<GenreResult>> {
+        self.search_genres(artist, release, "album").await
+    }
+
+    /// Genres of a single track, which on Spotify are those of its artists
+    pub async fn fetch_track_genres(
+        &self,
+        artist: &str,
+        title: &str,
+    ) -> Result<Option<GenreResult>> {
+        self.search_genres(artist, title, "track").await
+    }
+
+    /// Search for an album or track and return the genres of the best match
+    async fn search_genres(
+        &self,
+        artist: &str,
+        title: &str,
+        kind: &str,
+    ) -> Result<Option<GenreResult>> {
+        let token = self.access_token().await?;
+
+        // Unquoted, a field filter only applies to the value's first word
+        let quoted = |value: &str| format!("\"{}\"", value.replace('"', ""));
+        let query = format!("artist:{} {kind}:{}", quoted(artist), quoted(title));
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get("https://api.spotify.com/v1/search")
+            .bearer_auth(&token)
+            .query(&[("q", query.as_str()), ("type", kind), ("limit", "5")])
+            .send()
+            .await
+            .context("Failed to send Spotify request")?;
+
+        fetchers::check_rate_limit("spotify", response.status())?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let search_result: SpotifySearchResponse = response.json().await?;
+        let items = search_result
+            .albums
+            .or(search_result.tracks)
+            .map(|page| page.items)
+            .unwrap_or_default();
+
+        let scored = items.into_iter().map(|item| {
+            let score = matching::score(artist, title, &item.artist_name(), &item.name);
+            (item, score)
+        });
+
+        let Some((item, score)) = matching::best(scored) else {
+            return Ok(None);
+        };
+        let matched = format!("{} - {}", item.artist_name(), item.name);
+
+        // Album genres are almost always empty, artist genres are what Spotify actually curates
+        let genres = if item.genres.is_empty() {
+            let ids: Vec<_> = item.artists.into_iter().map(|a| a.id).collect();
+            if ids.is_empty() {
+                return Ok(None);
+            }
+            self.lookup_artist_genres(&token, &ids.join(",")).await?
+        } else {
+            item.genres
+        };
+
+        Ok(Some(GenreResult {
+            matched,
+            score,
+            genres,
+        }))
+    }
+
+    async fn lookup_artist_genres(&self, token: &str, ids: &str) -> Result<Vec<String>> {
+        self.limiter.wait().await;
+        let response = self
+            .client
+            .get("https://api.spotify.com/v1/artists")
+            .bearer_auth(token)
+            .query(&[("ids", ids)])
+            .send()
+            .await?;
+
+        fetchers::check_rate_limit("spotify", response.status())?;
+        if !response.status().is_success() {
+            return Ok(vec![]);
+        }
+
+        let artists: SpotifyArtistsResponse = response.json().await?;
+
+        Ok(artists
+            .artists
+            .into_iter()
+            .flatten()
+            .flat_map(|a| a.genres)
+            .collect())
+    }
+
+    /// Return a client-credentials access token, refreshing it once it expires
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+
+        if let Some((value, expires_at)) = token.as_ref()
+            && Instant::now() < *expires_at
+        {
+            return Ok(value.clone());
+        }
+
+        let response = self
+            .client
+            .post("https://accounts.spotify.com/api/token")
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await
+            .context("Failed to request Spotify access token")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Spotify token request failed with status: {}",
+                response.status()
+            );
+        }
+
+        let auth: SpotifyToken = response.json().await?;
+
+        // Refresh a little early so a token never expires mid-request
+        let expires_at = Instant::now() + Duration::from_secs(auth.expires_in.saturating_sub(60));
+        *token = Some((auth.access_token.clone(), expires_at));
+
+        Ok(auth.access_token)
+    }
+}
+
+// --- Serde Structs ---
+
+#[derive(Deserialize, Debug)]
+struct SpotifyToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpotifySearchResponse {
+    albums: Option<SpotifyPage>,
+    tracks: Option<SpotifyPage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpotifyPage {
+    items: Vec<SpotifyItem>,
+}
+
+/// Album or track search result, tracks never carry genres
+#[derive(Deserialize, Debug)]
+struct SpotifyItem {
+    name: String,
+    #[serde(default)]
+    genres: Vec<String>,
+    #[serde(default)]
+    artists: Vec<SpotifyArtistRef>,
+}
+
+impl SpotifyItem {
+    fn artist_name(&self) -> String {
+        self.artists
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct SpotifyArtistRef {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpotifyArtistsResponse {
+    artists: Vec<Option<SpotifyArtist>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpotifyArtist {
+    #[serde(default)]
+    genres: Vec<String>,
+}
@@ -0,0 +1,466 @@
+use crate::config;
+use crate::tagging::{self, open_tagged_file};
+use anyhow::{Context, Result};
+use lofty::config::WriteOptions;
+use lofty::picture::{Picture, PictureType};
+use lofty::prelude::*;
+use lofty::tag::{ItemKey, ItemValue, Tag, TagItem, TagType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tag keys hakunadata writes, and the names they're recorded under
+///
+/// Custom fields like STYLE aren't visible through the generic tag, they're
+/// read through the concrete format and recorded separately.
+const JOURNALED_KEYS: &[(&str, ItemKey)] = &[
+    ("artist", ItemKey::TrackArtist),
+    ("album", ItemKey::AlbumTitle),
+    ("genre", ItemKey::Genre),
+    ("label", ItemKey::Label),
+    ("publisher", ItemKey::Publisher),
+    ("year", ItemKey::RecordingDate),
+    ("original_date", ItemKey::OriginalReleaseDate),
+    ("country", ItemKey::ReleaseCountry),
+    ("catno", ItemKey::CatalogNumber),
+    ("musicbrainz_release_id", ItemKey::MusicBrainzReleaseId),
+    (
+        "musicbrainz_release_group_id",
+        ItemKey::MusicBrainzReleaseGroupId,
+    ),
+    ("musicbrainz_recording_id", ItemKey::MusicBrainzRecordingId),
+    ("musicbrainz_artist_id", ItemKey::MusicBrainzArtistId),
+    (
+        "musicbrainz_release_artist_id",
+        ItemKey::MusicBrainzReleaseArtistId,
+    ),
+];
+
+/// One change made during a run, as a line of `journal.jsonl`
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum JournalEntry {
+    /// Tags written to a file, with the values they replaced
+    Tags {
+        path: PathBuf,
+        /// Which of the file's tags was written, the primary one if unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag_type: Option<String>,
+        /// Files that had no tag get theirs removed again
+        had_tag: bool,
+        fields: BTreeMap<String, Vec<String>>,
+        /// Custom fields like STYLE, for tag types that can hold them
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        custom_fields: BTreeMap<String, Vec<String>>,
+        /// Whether the front cover was replaced
+        artwork: bool,
+        /// File in the run directory holding the original front cover
+        original_cover: Option<String>,
+    },
+    /// Cover image saved next to the tracks
+    Cover { path: PathBuf },
+}
+
+/// Tag values of a file before anything is written to it
+pub struct Snapshot {
+    tag_type: TagType,
+    had_tag: bool,
+    fields: BTreeMap<String, Vec<String>>,
+    custom_fields: BTreeMap<String, Vec<String>>,
+    cover: Option<Picture>,
+}
+
+impl Snapshot {
+    pub fn new(
+        tag: Option<&Tag>,
+        tag_type: TagType,
+        custom_fields: BTreeMap<String, Vec<String>>,
+    ) -> Self {
+        let Some(tag) = tag else {
+            return Self {
+                tag_type,
+                had_tag: false,
+                fields: BTreeMap::new(),
+                custom_fields: BTreeMap::new(),
+                cover: None,
+            };
+        };
+
+        let fields = JOURNALED_KEYS
+            .iter()
+            .map(|(name, key)| {
+                let values = tag.get_strings(*key).map(str::to_string).collect();
+                ((*name).to_string(), values)
+            })
+            .collect();
+
+        let cover = tag
+            .pictures()
+            .iter()
+            .find(|p| p.pic_type() == PictureType::CoverFront)
+            .cloned();
+
+        Self {
+            tag_type,
+            had_tag: true,
+            fields,
+            custom_fields,
+            cover,
+        }
+    }
+}
+
+/// Record of the files changed by one `--write` run
+pub struct Journal {
+    id: String,
+    dir: PathBuf,
+    file: Mutex<File>,
+    covers: AtomicUsize,
+}
+
+impl Journal {
+    /// Start a new run under `$XDG_DATA_HOME/hakunadata/runs` (or `~/.local/share/hakunadata/runs`)
+    pub fn create() -> Result<Self> {
+        let runs = runs_dir()?;
+        fs::create_dir_all(&runs)
+            .with_context(|| format!("Failed to create journal directory: {}", runs.display()))?;
+
+        // Two runs started within the same second get a numbered suffix
+        let timestamp = run_timestamp();
+        let mut id = timestamp.clone();
+        let mut n = 1;
+        let dir = loop {
+            let dir = runs.join(&id);
+            match fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    n += 1;
+                    id = format!("{timestamp}-{n}");
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to create journal directory: {}", dir.display())
+                    });
+                }
+            }
+        };
+
+        let path = dir.join("journal.jsonl");
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create journal: {}", path.display()))?;
+
+        Ok(Self {
+            id,
+            dir,
+            file: Mutex::new(file),
+            covers: AtomicUsize::new(0),
+        })
+    }
+
+    /// Drop the run if nothing was written, otherwise tell how to undo it
+    pub fn finish(self) -> Result<()> {
+        let written = read_entries(&self.dir)?.len();
+        if written == 0 {
+            fs::remove_dir_all(&self.dir)
+                .with_context(|| format!("Failed to remove journal: {}", self.dir.display()))?;
+        } else {
+            println!(
+                "Recorded {written} changes as run {}, revert with `hakunadata undo --run {}`",
+                self.id, self.id
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Record the original tags of a file that was just written
+    pub fn record_tags(&self, path: &Path, snapshot: Snapshot, artwork: bool) -> Result<()> {
+        // Only keep the old cover if it was actually replaced
+        let original_cover = match snapshot.cover.filter(|_| artwork) {
+            Some(picture) => {
+                let name = format!("{}.cover", self.covers.fetch_add(1, Ordering::Relaxed));
+                let cover_path = self.dir.join(&name);
+                fs::write(&cover_path, picture.data()).with_context(|| {
+                    format!("Failed to save original cover: {}", cover_path.display())
+                })?;
+                Some(name)
+            }
+            None => None,
+        };
+
+        self.append(&JournalEntry::Tags {
+            path: path.to_path_buf(),
+            tag_type: Some(tag_type_name(snapshot.tag_type).to_string()),
+            had_tag: snapshot.had_tag,
+            fields: snapshot.fields,
+            custom_fields: snapshot.custom_fields,
+            artwork,
+            original_cover,
+        })
+    }
+
+    /// Record a cover image that was saved next to the tracks
+    pub fn record_cover(&self, path: &Path) -> Result<()> {
+        self.append(&JournalEntry::Cover {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn append(&self, entry: &JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        // A whole line per write, so entries from concurrent albums don't interleave
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Journal lock poisoned"))?;
+        file.write_all(line.as_bytes())
+            .context("Failed to write journal entry")?;
+
+        Ok(())
+    }
+}
+
+/// Print the runs that can be undone, oldest first
+pub fn list_runs() -> Result<()> {
+    let runs = run_ids()?;
+    if runs.is_empty() {
+        println!("No runs to undo");
+        return Ok(());
+    }
+
+    for id in runs {
+        let entries = read_entries(&runs_dir()?.join(&id)).map_or(0, |entries| entries.len());
+        println!("{id}  {entries} changes");
+    }
+
+    Ok(())
+}
+
+/// Restore everything changed by a run (the latest one by default)
+pub fn undo(run: Option<&str>) -> Result<()> {
+    let id = match run {
+        Some(id) => id.to_string(),
+        None => run_ids()?.pop().context("No runs to undo")?,
+    };
+
+    let dir = runs_dir()?.join(&id);
+    if !dir.is_dir() {
+        anyhow::bail!("No such run: {id}");
+    }
+
+    let entries = read_entries(&dir)?;
+    println!("Undoing run {id} ({} changes)", entries.len());
+
+    // Newest first, in case a file was written more than once
+    let mut failed = 0;
+    for entry in entries.into_iter().rev() {
+        if let Err(e) = undo_entry(&dir, entry) {
+            eprintln!("{e:?}");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!(
+            "Failed to undo {failed} changes, the run is kept in {}",
+            dir.display()
+        );
+    }
+
+    fs::remove_dir_all(&dir)
+        .with_context(|| format!("Failed to remove journal: {}", dir.display()))?;
+
+    Ok(())
+}
+
+fn undo_entry(dir: &Path, entry: JournalEntry) -> Result<()> {
+    match entry {
+        JournalEntry::Cover { path } => {
+            // Already gone is as good as removed
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove cover: {}", path.display()))?;
+            }
+            println!("Removed {}", path.display());
+        }
+        JournalEntry::Tags {
+            path,
+            tag_type,
+            had_tag,
+            fields,
+            custom_fields,
+            artwork,
+            original_cover,
+        } => {
+            let path_display = path.display();
+            let mut tagged_file = open_tagged_file(&path)?;
+            let tag_type = match tag_type {
+                Some(name) => parse_tag_type(&name)
+                    .with_context(|| format!("Unknown tag type '{name}' in journal"))?,
+                None => tagged_file.primary_tag_type(),
+            };
+
+            if !had_tag {
+                tag_type
+                    .remove_from_path(&path)
+                    .with_context(|| format!("Failed to remove tag from {path_display}"))?;
+                println!("Restored {path_display}");
+                return Ok(());
+            }
+
+            let tag = tagged_file
+                .tag_mut(tag_type)
+                .with_context(|| format!("Tag missing from {path_display}"))?;
+
+            for (name, key) in JOURNALED_KEYS {
+                tag.remove_key(*key);
+                for value in fields.get(*name).into_iter().flatten() {
+                    tag.push(TagItem::new(*key, ItemValue::Text(value.clone())));
+                }
+            }
+
+            if artwork {
+                tag.remove_picture_type(PictureType::CoverFront);
+                if let Some(name) = original_cover {
+                    let cover_path = dir.join(name);
+                    let mut reader =
+                        BufReader::new(File::open(&cover_path).with_context(|| {
+                            format!("Failed to open original cover: {}", cover_path.display())
+                        })?);
+                    let mut picture = Picture::from_reader(&mut reader)?;
+                    picture.set_pic_type(PictureType::CoverFront);
+                    tag.push_picture(picture);
+                }
+            }
+
+            // Custom fields go back to what they were, or away if the file
+            // had none; journals that recorded none are saved as before
+            let custom_fields: Vec<(&str, Vec<String>)> = custom_fields
+                .iter()
+                .map(|(name, values)| (name.as_str(), values.clone()))
+                .collect();
+            if custom_fields.is_empty() {
+                tagged_file
+                    .save_to_path(&path, WriteOptions::default())
+                    .map_err(anyhow::Error::from)
+            } else {
+                tagging::save_with_custom_fields(tag, &custom_fields, &path)
+                    .map_err(anyhow::Error::from)
+            }
+            .with_context(|| format!("Failed to restore tags of {path_display}"))?;
+            println!("Restored {path_display}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Name a tag type is recorded under
+fn tag_type_name(tag_type: TagType) -> &'static str {
+    match tag_type {
+        TagType::Ape => "ape",
+        TagType::Id3v1 => "id3v1",
+        TagType::Id3v2 => "id3v2",
+        TagType::Mp4Ilst => "mp4_ilst",
+        TagType::VorbisComments => "vorbis_comments",
+        TagType::RiffInfo => "riff_info",
+        TagType::AiffText => "aiff_text",
+        _ => "unknown",
+    }
+}
+
+fn parse_tag_type(name: &str) -> Option<TagType> {
+    [
+        TagType::Ape,
+        TagType::Id3v1,
+        TagType::Id3v2,
+        TagType::Mp4Ilst,
+        TagType::VorbisComments,
+        TagType::RiffInfo,
+        TagType::AiffText,
+    ]
+    .into_iter()
+    .find(|&tag_type| tag_type_name(tag_type) == name)
+}
+
+fn read_entries(dir: &Path) -> Result<Vec<JournalEntry>> {
+    let path = dir.join("journal.jsonl");
+    let file =
+        File::open(&path).with_context(|| format!("Failed to open journal: {}", path.display()))?;
+
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line?;
+            serde_json::from_str(&line)
+                .with_context(|| format!("Invalid journal entry in {}", path.display()))
+        })
+        .collect()
+}
+
+/// IDs of the runs in the journal directory, oldest first
+fn run_ids() -> Result<Vec<String>> {
+    let runs = runs_dir()?;
+    let Ok(entries) = fs::read_dir(&runs) else {
+        return Ok(vec![]);
+    };
+
+    let mut ids: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().join("journal.jsonl").exists())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    ids.sort_by_key(|id| run_order(id));
+    Ok(ids)
+}
+
+/// Sort key of a run ID, so `-10` comes after `-2` among runs of the same second
+///
+/// Timestamps sort chronologically as they are, the suffix has to be parsed.
+fn run_order(id: &str) -> (String, u32) {
+    match id.match_indices('-').nth(1) {
+        Some((i, _)) => (id[..i].to_string(), id[i + 1..].parse().unwrap_or(0)),
+        None => (id.to_string(), 1),
+    }
+}
+
+fn runs_dir() -> Result<PathBuf> {
+    config::data_path("runs")
+}
+
+/// Current UTC time as `YYYYMMDD-HHMMSS`
+fn run_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, time) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
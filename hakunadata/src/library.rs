@@ -0,0 +1,603 @@
+use crate::context::AppContext;
+use crate::fetchers::RateLimited;
+use crate::journal::Journal;
+use crate::metadata::fetch::{FetchedMetadata, merge_compilation, process_query, process_track_query};
+use crate::metadata::matching::{self, VARIOUS_ARTISTS};
+use crate::path_pattern::PathPattern;
+use crate::progress;
+use crate::resume::{self, ResumeState};
+use crate::tagging::{Track, format_proposed_tags, read_track, write_file};
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use lofty::picture::Picture;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::sync::Semaphore;
+use walkdir::WalkDir;
+
+/// Audio file extensions picked up when walking a directory
+const AUDIO_EXTENSIONS: &[&str] = &["flac", "mp3", "ogg", "m4a"];
+
+/// Tracks sharing the same artist and album
+pub struct Album {
+    pub artist: String,
+    pub album: String,
+    /// Set for a single track that's looked up on its own
+    pub title: Option<String>,
+    /// Tracks by various artists, each looked up by its title
+    pub compilation: bool,
+    pub tracks: Vec<Track>,
+}
+
+/// Outcome of processing a single album, used for the final summary table
+struct AlbumSummary {
+    name: String,
+    /// Identifies the album in the resume state
+    key: String,
+    tracks: usize,
+    written: usize,
+    genres: String,
+    label: String,
+    status: AlbumStatus,
+}
+
+/// How far an album got, shown in the summary table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AlbumStatus {
+    Ok,
+    /// Some tracks couldn't be written
+    Partial,
+    NoMatch,
+    /// `--offline` found a source missing from the cache
+    NotCached,
+    /// A source turned the lookup down, the run stops
+    RateLimited,
+    Error,
+}
+
+impl fmt::Display for AlbumStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::Partial => "partial",
+            Self::NoMatch => "no match",
+            Self::NotCached => "not cached",
+            Self::RateLimited => "rate limited",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// Recursively collect all supported audio files below a directory
+pub fn collect_audio_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    for entry in WalkDir::new(root).into_iter().filter_map(Result::ok) {
+        if entry.file_type().is_file()
+            && entry
+                .path()
+                .extension()
+                .and_then(|s| s.to_str())
+                .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext))
+        {
+            files.push(entry.path().to_path_buf());
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Group files by their artist and album, skipping files that can't be read
+///
+/// Compilations are grouped under various artists, whatever their track
+/// artists. Other tracks looked up by title (singles or everything with
+/// `by_track`) get a group of their own. `compilation` overrides which
+/// tracks count as compilation tracks.
+pub fn group_albums(
+    files: &[PathBuf],
+    pattern: Option<&PathPattern>,
+    by_track: bool,
+    compilation: Option<bool>,
+) -> Vec<Album> {
+    let mut tracks = Vec::new();
+    for file_path in files {
+        match read_track(file_path, pattern) {
+            Ok(Some(track)) => tracks.push(track),
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to read tags from {}: {e:?}", file_path.display()),
+        }
+    }
+
+    match compilation {
+        Some(compilation) => {
+            for track in &mut tracks {
+                track.compilation = compilation;
+            }
+        }
+        None => mark_compilations(&mut tracks),
+    }
+
+    let mut albums: BTreeMap<(String, String, Option<String>), Vec<Track>> = BTreeMap::new();
+    for track in tracks {
+        let key = if track.compilation {
+            (VARIOUS_ARTISTS.to_string(), track.album.clone(), None)
+        } else {
+            (
+                track.artist.clone(),
+                track.album.clone(),
+                track.lookup_title(by_track).map(str::to_string),
+            )
+        };
+        albums.entry(key).or_default().push(track);
+    }
+
+    albums
+        .into_iter()
+        .map(|((artist, album, title), tracks)| Album {
+            compilation: tracks[0].compilation,
+            artist,
+            album,
+            title,
+            tracks,
+        })
+        .collect()
+}
+
+/// Flag the tracks of albums whose files in a directory have different
+/// artists and no album artist of their own as compilation tracks
+fn mark_compilations(tracks: &mut [Track]) {
+    let mut artists: HashMap<(Option<&Path>, &str), BTreeSet<&str>> = HashMap::new();
+    for track in tracks.iter() {
+        if track
+            .album_artist
+            .as_deref()
+            .is_some_and(|artist| !matching::is_various(artist))
+        {
+            continue;
+        }
+        artists
+            .entry((track.path.parent(), &track.album))
+            .or_default()
+            .insert(&track.artist);
+    }
+
+    let compilations: BTreeSet<(Option<PathBuf>, String)> = artists
+        .into_iter()
+        .filter(|(_, artists)| artists.len() > 1)
+        .map(|((dir, album), _)| (dir.map(Path::to_path_buf), album.to_string()))
+        .collect();
+
+    for track in tracks {
+        let key = (
+            track.path.parent().map(Path::to_path_buf),
+            track.album.clone(),
+        );
+        if compilations.contains(&key) {
+            track.compilation = true;
+        }
+    }
+}
+
+/// Walk a music library, fetch metadata once per album and apply it to every track
+///
+/// Up to `jobs` albums are processed at once, each printed as soon as it's done.
+pub async fn process_library(
+    ctx: &AppContext,
+    root: &Path,
+    read: bool,
+    write: bool,
+    jobs: usize,
+    resume: bool,
+) -> Result<()> {
+    if !root.is_dir() {
+        return Err(anyhow::anyhow!("Not a directory: {}", root.display()));
+    }
+
+    let files = collect_audio_files(root);
+    let albums = group_albums(
+        &files,
+        ctx.path_pattern.as_ref(),
+        ctx.by_track,
+        ctx.compilation,
+    );
+
+    println!(
+        "Found {} albums ({} files) in {}",
+        albums.len(),
+        files.len(),
+        root.display()
+    );
+
+    // Only written albums count as done, a read-only run changes nothing to resume
+    let state = write.then(|| ResumeState::open(root, resume)).transpose()?;
+    let mut albums = albums;
+    if let Some(state) = &state {
+        let before = albums.len();
+        albums.retain(|album| !state.is_done(&resume::key(album)));
+        if albums.len() < before {
+            println!(
+                "Skipping {} albums finished by the interrupted run",
+                before - albums.len()
+            );
+        }
+    }
+    let total = albums.len();
+
+    // Fetchers rate-limit themselves, this only bounds how much is in flight
+    let semaphore = Semaphore::new(jobs);
+
+    let mut pending: FuturesUnordered<_> = albums
+        .into_iter()
+        .map(|album| {
+            let semaphore = &semaphore;
+            async move {
+                let _permit = semaphore
+                    .acquire()
+                    .await
+                    .expect("semaphore is never closed");
+                process_album(ctx, album, read, write).await
+            }
+        })
+        .collect();
+
+    progress::start(total);
+
+    let mut summaries = Vec::new();
+    let mut finished = 0;
+    let mut throttled = false;
+    while let Some(summary) = pending.next().await {
+        progress::inc(summary.status != AlbumStatus::Ok);
+
+        // Anything short of fully written gets another go with --resume,
+        // a source that was down looks just like no match
+        if let Some(state) = &state
+            && summary.status == AlbumStatus::Ok
+        {
+            match state.record(&summary.key) {
+                Ok(()) => finished += 1,
+                Err(e) => progress::eprintln(format!("Warning: {e:?}")),
+            }
+        }
+
+        throttled = summary.status == AlbumStatus::RateLimited;
+        summaries.push(summary);
+        if throttled {
+            break;
+        }
+    }
+    // Albums still in flight are dropped at an await, before writing anything
+    drop(pending);
+    progress::finish();
+
+    // Albums finish in any order, keep the table sorted like the library
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    print_summary(&summaries, write);
+
+    if throttled {
+        let hint = if write {
+            ", run again with --resume to carry on"
+        } else {
+            ""
+        };
+        return Err(anyhow::anyhow!(
+            "Stopped after {} of {total} albums because a source is rate limiting{hint}",
+            summaries.len()
+        ));
+    }
+
+    if let Some(state) = state
+        && finished == total
+    {
+        state.finish()?;
+    }
+
+    Ok(())
+}
+
+/// Fetch metadata for one album (or standalone track) and write it to its files
+async fn process_album(ctx: &AppContext, album: Album, read: bool, write: bool) -> AlbumSummary {
+    let name = match &album.title {
+        Some(title) => format!("{} - {title} (track on {})", album.artist, album.album),
+        None => format!("{} - {}", album.artist, album.album),
+    };
+
+    let mut summary = AlbumSummary {
+        name: name.clone(),
+        key: resume::key(&album),
+        tracks: album.tracks.len(),
+        written: 0,
+        genres: String::new(),
+        label: String::new(),
+        status: AlbumStatus::Ok,
+    };
+
+    if album.compilation {
+        return process_compilation(ctx, album, read, write, summary).await;
+    }
+
+    let query = match &album.title {
+        Some(title) => process_track_query(ctx, &album.artist, title).await,
+        None => process_query(ctx, &album.artist, &album.album).await,
+    };
+
+    let mut result = match query {
+        Ok(result) => result,
+        Err(e) => {
+            progress::eprintln(format!("Failed to fetch metadata for {name}: {e:?}"));
+            summary.status = failure(&e);
+            return summary;
+        }
+    };
+
+    summary.genres = result.genres.join("/");
+    summary.label = result.labels.first().cloned().unwrap_or_default();
+
+    if !result.uncached.is_empty() {
+        progress::println(format!(
+            "Processing: {name}\nNot cached: {}",
+            result.uncached.join(", ")
+        ));
+        summary.status = AlbumStatus::NotCached;
+        return summary;
+    }
+
+    if result.genres.is_empty() && result.labels.is_empty() {
+        progress::println(format!("Processing: {name}\nNo match"));
+        summary.status = AlbumStatus::NoMatch;
+        return summary;
+    }
+
+    if read {
+        progress::print(format!(
+            "Processing: {name}\n{}",
+            format_proposed_tags(&result)
+        ));
+    } else {
+        progress::println(format!("Processing: {name}"));
+    }
+
+    if write {
+        // Covers go next to the files instead of into every track
+        if let Some(picture) = result.artwork.take() {
+            write_covers(&album.tracks, &picture, ctx.journal.as_ref());
+        }
+
+        for track in &album.tracks {
+            match write_file(track, &result, &ctx.policy, ctx.journal.as_ref()) {
+                Ok(()) => summary.written += 1,
+                Err(e) => progress::eprintln(format!(
+                    "Failed to process file {}: {e:?}",
+                    track.path.display()
+                )),
+            }
+        }
+
+        if summary.written < summary.tracks {
+            summary.status = AlbumStatus::Partial;
+        }
+    }
+
+    summary
+}
+
+/// Fetch the release details of a compilation once, and the genres of each
+/// of its tracks by title
+async fn process_compilation(
+    ctx: &AppContext,
+    album: Album,
+    read: bool,
+    write: bool,
+    mut summary: AlbumSummary,
+) -> AlbumSummary {
+    let name = summary.name.clone();
+
+    let mut release = match process_query(ctx, VARIOUS_ARTISTS, &album.album).await {
+        Ok(release) => release,
+        Err(e) => {
+            progress::eprintln(format!("Failed to fetch metadata for {name}: {e:?}"));
+            summary.status = failure(&e);
+            return summary;
+        }
+    };
+    let artwork = release.artwork.take();
+
+    let mut results = Vec::new();
+    for track in &album.tracks {
+        let result = match &track.title {
+            Some(title) => match process_track_query(ctx, &track.artist, title).await {
+                Ok(result) => merge_compilation(result, &release),
+                Err(e) if e.is::<RateLimited>() => {
+                    progress::eprintln(format!("Failed to fetch metadata for {name}: {e:?}"));
+                    summary.status = failure(&e);
+                    return summary;
+                }
+                Err(e) => {
+                    progress::eprintln(format!(
+                        "Failed to fetch metadata for {} - {title}: {e:?}",
+                        track.artist
+                    ));
+                    summary.status = AlbumStatus::Partial;
+                    merge_compilation(FetchedMetadata::default(), &release)
+                }
+            },
+            None => merge_compilation(FetchedMetadata::default(), &release),
+        };
+        results.push(result);
+    }
+
+    let genres: BTreeSet<&str> = results
+        .iter()
+        .flat_map(|result| result.genres.iter().map(String::as_str))
+        .collect();
+    summary.genres = genres.into_iter().collect::<Vec<_>>().join("/");
+    summary.label = release.labels.first().cloned().unwrap_or_default();
+
+    // Merged results carry the release's uncached sources as well
+    let mut uncached: Vec<&str> = results
+        .iter()
+        .flat_map(|result| result.uncached.iter().copied())
+        .chain(release.uncached.iter().copied())
+        .collect();
+    uncached.sort_unstable();
+    uncached.dedup();
+    if !uncached.is_empty() {
+        progress::println(format!(
+            "Processing: {name}\nNot cached: {}",
+            uncached.join(", ")
+        ));
+        summary.status = AlbumStatus::NotCached;
+        return summary;
+    }
+
+    if summary.genres.is_empty() && release.labels.is_empty() {
+        progress::println(format!("Processing: {name}\nNo match"));
+        summary.status = AlbumStatus::NoMatch;
+        return summary;
+    }
+
+    progress::println(format!("Processing: {name}"));
+    if read {
+        for (track, result) in album.tracks.iter().zip(&results) {
+            let title = track.title.as_deref().unwrap_or("(untitled)");
+            progress::print(format!(
+                "{} - {title}\n{}",
+                track.artist,
+                format_proposed_tags(result)
+            ));
+        }
+    }
+
+    if write {
+        if let Some(picture) = &artwork {
+            write_covers(&album.tracks, picture, ctx.journal.as_ref());
+        }
+
+        for (track, result) in album.tracks.iter().zip(&results) {
+            match write_file(track, result, &ctx.policy, ctx.journal.as_ref()) {
+                Ok(()) => summary.written += 1,
+                Err(e) => progress::eprintln(format!(
+                    "Failed to process file {}: {e:?}",
+                    track.path.display()
+                )),
+            }
+        }
+
+        if summary.written < summary.tracks {
+            summary.status = AlbumStatus::Partial;
+        }
+    }
+
+    summary
+}
+
+/// Status of an album whose lookup failed, telling rate limits apart so
+/// the run can stop
+fn failure(e: &anyhow::Error) -> AlbumStatus {
+    if e.is::<RateLimited>() {
+        AlbumStatus::RateLimited
+    } else {
+        AlbumStatus::Error
+    }
+}
+
+/// Save the album cover as `cover.<ext>` in every directory holding its tracks
+fn write_covers(tracks: &[Track], picture: &Picture, journal: Option<&Journal>) {
+    let ext = picture
+        .mime_type()
+        .and_then(|mime| mime.ext())
+        .unwrap_or("jpg");
+
+    let dirs: BTreeSet<&Path> = tracks.iter().filter_map(|t| t.path.parent()).collect();
+
+    for dir in dirs {
+        let cover_path = dir.join(format!("cover.{ext}"));
+        if cover_path.exists() {
+            progress::println(format!(
+                "Cover already exists, skipping: {}",
+                cover_path.display()
+            ));
+            continue;
+        }
+
+        let written = std::fs::write(&cover_path, picture.data())
+            .with_context(|| format!("Failed to write cover: {}", cover_path.display()))
+            .and_then(|()| journal.map_or(Ok(()), |j| j.record_cover(&cover_path)));
+
+        match written {
+            Ok(()) => progress::println(format!("Cover written to {}", cover_path.display())),
+            Err(e) => progress::eprintln(format!("{e:?}")),
+        }
+    }
+}
+
+/// Print a table with one row per processed album
+fn print_summary(summaries: &[AlbumSummary], write: bool) {
+    let rows: Vec<[String; 5]> = summaries
+        .iter()
+        .map(|s| {
+            let tracks = if write {
+                format!("{}/{}", s.written, s.tracks)
+            } else {
+                s.tracks.to_string()
+            };
+            [
+                s.name.clone(),
+                tracks,
+                s.genres.clone(),
+                s.label.clone(),
+                s.status.to_string(),
+            ]
+        })
+        .collect();
+
+    let header = ["Album", "Tracks", "Genres", "Label", "Status"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: [&str; 5]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    println!();
+    print_row(header);
+    for row in &rows {
+        print_row(row.each_ref().map(String::as_str));
+    }
+
+    let failed = summaries
+        .iter()
+        .filter(|s| s.status != AlbumStatus::Ok)
+        .count();
+    println!();
+    println!(
+        "Albums: {} ({failed} without a full match)",
+        summaries.len()
+    );
+
+    let uncached: Vec<&AlbumSummary> = summaries
+        .iter()
+        .filter(|s| s.status == AlbumStatus::NotCached)
+        .collect();
+    if !uncached.is_empty() {
+        println!();
+        println!(
+            "Albums needing a network pass (run again without --offline): {}",
+            uncached.len()
+        );
+        for summary in uncached {
+            println!("  {}", summary.name);
+        }
+    }
+}
@@ -1,17 +1,41 @@
+mod cache;
+mod config;
 mod context;
 mod fetchers;
+mod journal;
+mod library;
 mod metadata;
+mod path_pattern;
+mod progress;
+mod report;
+mod resume;
 mod tagging;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use cache::Cache;
+use clap::{Parser, Subcommand};
 use context::AppContext;
+use fetchers::Source;
+use fetchers::bandcamp::BandcampClient;
+use fetchers::coverart::CoverArtClient;
 use fetchers::discogs::DiscogsClient;
+use fetchers::lastfm::LastFmClient;
 use fetchers::musicbrainz::MusicBrainzClient;
-use metadata::fetch::process_query;
-use std::path::Path;
-use tagging::{print_metadata, process_file};
-use walkdir::WalkDir;
+use fetchers::spotify::SpotifyClient;
+use journal::Journal;
+use library::{collect_audio_files, process_library};
+use metadata::fetch::{process_query, process_track_query};
+use metadata::filter::TagFilter;
+use metadata::genres::GenreMap;
+use metadata::matching::{Format, Preference};
+use path_pattern::PathPattern;
+use report::report_library;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tagging::{
+    GenreMode, SubgenreMode, TagField, TagTargets, WritePolicy, print_metadata, process_file,
+};
 
 static EXAMPLES: &str = r"EXAMPLES:
     Fetch metadata for an artist and album:
@@ -27,7 +51,25 @@ static EXAMPLES: &str = r"EXAMPLES:
     hakunadata --read /path/to/music
 
     Write tags to all files in a directory:
-    hakunadata --write /path/to/music";
+    hakunadata --write /path/to/music
+
+    Tag untagged rips laid out as Artist/Album/01 Title.flac:
+    hakunadata --dir /path/to/rips --from-path 'Artist/Album/Track' --write
+
+    Fetch genres for a single track rather than its album:
+    hakunadata --sources musicbrainz,lastfm --artist 'Burial' --track 'Archangel'
+
+    Combine genres from several sources:
+    hakunadata --sources discogs,lastfm --read file.mp3
+
+    Tag a whole library, fetching metadata once per album:
+    hakunadata --dir /path/to/music --write
+
+    Revert the tags written by the last run:
+    hakunadata undo
+
+    Check genre and label coverage of a library before retagging it:
+    hakunadata report --dir /path/to/music";
 
 #[derive(Parser, Debug)]
 #[command(
@@ -35,9 +77,14 @@ static EXAMPLES: &str = r"EXAMPLES:
     version,
     about,
     long_about = None,
-    after_help = EXAMPLES
+    after_help = EXAMPLES,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Artist name
     #[arg(long)]
     artist: Option<String>,
@@ -46,10 +93,38 @@ struct Args {
     #[arg(long)]
     album: Option<String>,
 
+    /// Track title, to look up a single recording instead of an album
+    #[arg(long, requires = "artist", conflicts_with = "album")]
+    track: Option<String>,
+
     /// File(s) to process
-    #[arg(required_unless_present_any = ["artist", "album"])]
+    #[arg(required_unless_present_any = ["artist", "album", "track", "dir"])]
     files: Vec<String>,
 
+    /// Music library to process, grouped by album
+    #[arg(long, conflicts_with = "files")]
+    dir: Option<PathBuf>,
+
+    /// Skip the albums an interrupted --dir --write run already finished
+    #[arg(long, default_value_t = false, requires_all = ["dir", "write"])]
+    resume: bool,
+
+    /// Number of albums processed at once with --dir
+    #[arg(long, default_value = "4", requires = "dir")]
+    jobs: NonZeroUsize,
+
+    /// Pattern for artist and album of untagged files, e.g. 'Artist/Album/Track' or 'Artist - Album/*'
+    #[arg(long, value_name = "PATTERN")]
+    from_path: Option<PathPattern>,
+
+    /// Look up every file by its title instead of just singles and compilation tracks
+    #[arg(long, default_value_t = false)]
+    by_track: bool,
+
+    /// Treat every file as a compilation track (or none with =false) instead of detecting them
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    compilation: Option<bool>,
+
     /// Write tags to file
     #[arg(short, long, default_value_t = false)]
     write: bool,
@@ -57,30 +132,238 @@ struct Args {
     /// Read tags from file and show what would be written
     #[arg(short, long, default_value_t = true)]
     read: bool,
+
+    /// Optional tags to write alongside genre and label [default: all]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    fields: Vec<TagField>,
+
+    /// Replace the genres in the file or append to them
+    #[arg(long, value_enum)]
+    genre_mode: Option<GenreMode>,
+
+    /// Maximum number of genres to write
+    #[arg(long)]
+    max_genres: Option<usize>,
+
+    /// Write one genre item per value instead of a single joined string
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    multi_value: Option<bool>,
+
+    /// Separator for joined genre strings [default: /]
+    #[arg(long)]
+    separator: Option<String>,
+
+    /// Where to write subgenres (Discogs styles) [default: none]
+    #[arg(long, value_enum)]
+    subgenres: Option<SubgenreMode>,
+
+    /// Overwrite a label that's already in the file [default: true]
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    overwrite_label: Option<bool>,
+
+    /// Which tags to write in files carrying several [default: primary]
+    ///
+    /// ID3v2 tags in FLAC files are read-only and left as they are, Vorbis
+    /// comments are the only tag written there.
+    #[arg(long, value_enum)]
+    tag_targets: Option<TagTargets>,
+
+    /// Metadata sources to query (defaults to Discogs if DISCOGS_TOKEN is set, MusicBrainz otherwise)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    sources: Vec<Source>,
+
+    /// Take genres, styles and year from the Discogs master release instead of a specific pressing
+    #[arg(long, default_value_t = false)]
+    discogs_master: bool,
+
+    /// Prefer pressings from this country, e.g. 'UK' or 'DE'
+    #[arg(long, value_name = "COUNTRY")]
+    prefer_country: Option<String>,
+
+    /// Prefer pressings in this format
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    prefer_format: Option<Format>,
+
+    /// Download the front cover and embed it (or save it as cover.jpg with --dir)
+    #[arg(long, default_value_t = false)]
+    fetch_art: bool,
+
+    /// Preferred maximum cover size in pixels
+    #[arg(long, default_value_t = 1200, requires = "fetch_art")]
+    max_size: u32,
+
+    /// Don't read or write the on-disk response cache
+    #[arg(long, default_value_t = false)]
+    no_cache: bool,
+
+    /// Only use cached responses, listing what needs a network pass
+    #[arg(long, default_value_t = false, conflicts_with_all = ["no_cache", "fetch_art"])]
+    offline: bool,
+
+    /// Proxy for every request, e.g. 'http://proxy:3128' [default: HTTP(S)_PROXY from the environment]
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Number of days cached responses stay valid
+    #[arg(long, default_value_t = 30)]
+    cache_ttl: u64,
+
+    /// Minimum similarity (0.0-1.0) between the query and a match for it to be used
+    #[arg(long, default_value_t = 0.7)]
+    min_score: f64,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Restore the tags changed by an earlier --write run
+    Undo {
+        /// Run to undo [default: the latest]
+        #[arg(long)]
+        run: Option<String>,
+
+        /// List the runs that can be undone
+        #[arg(long, conflicts_with = "run")]
+        list: bool,
+    },
+
+    /// Summarize the genres and labels already in a library, without fetching anything
+    Report {
+        /// Music library to scan
+        #[arg(long)]
+        dir: PathBuf,
+
+        /// Write the report as JSON to a file instead of printing it
+        #[arg(long, value_name = "FILE")]
+        json: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    let (discogs_client, mb_client) = if std::env::var("DISCOGS_TOKEN").is_ok() {
-        (
-            Some(DiscogsClient::new().context("Failed to init Discogs client")?),
-            None,
-        )
+    match args.command {
+        Some(Command::Undo { run, list }) => {
+            return if list {
+                journal::list_runs()
+            } else {
+                journal::undo(run.as_deref())
+            };
+        }
+        Some(Command::Report { dir, json }) => {
+            let separator = WritePolicy::load()?.separator;
+            return report_library(&dir, &separator, json.as_deref());
+        }
+        None => {}
+    }
+
+    let sources = if !args.sources.is_empty() {
+        args.sources
+    } else if std::env::var("DISCOGS_TOKEN").is_ok() {
+        vec![Source::Discogs]
     } else {
-        (
-            None,
-            Some(MusicBrainzClient::new().context("Failed to init MusicBrainz client")?),
-        )
+        vec![Source::Musicbrainz]
+    };
+
+    let proxy = args.proxy.as_deref();
+    let discogs_client = sources
+        .contains(&Source::Discogs)
+        .then(|| DiscogsClient::new(proxy).context("Failed to init Discogs client"))
+        .transpose()?;
+    let mb_client = sources
+        .contains(&Source::Musicbrainz)
+        .then(|| MusicBrainzClient::new(proxy).context("Failed to init MusicBrainz client"))
+        .transpose()?;
+    let lastfm_client = sources
+        .contains(&Source::Lastfm)
+        .then(|| LastFmClient::new(proxy).context("Failed to init Last.fm client"))
+        .transpose()?;
+    let spotify_client = sources
+        .contains(&Source::Spotify)
+        .then(|| SpotifyClient::new(proxy).context("Failed to init Spotify client"))
+        .transpose()?;
+    let bandcamp_client = sources
+        .contains(&Source::Bandcamp)
+        .then(|| BandcampClient::new(proxy).context("Failed to init Bandcamp client"))
+        .transpose()?;
+
+    let art_client = args
+        .fetch_art
+        .then(|| {
+            CoverArtClient::new(args.max_size, proxy).context("Failed to init cover art client")
+        })
+        .transpose()?;
+
+    // Flags take precedence over the [write] table in config.toml
+    let mut policy = WritePolicy::load()?;
+    if !args.fields.is_empty() {
+        policy.fields = args.fields;
+    }
+    if let Some(genre_mode) = args.genre_mode {
+        policy.genre_mode = genre_mode;
+    }
+    if let Some(max_genres) = args.max_genres {
+        policy.max_genres = Some(max_genres);
+    }
+    if let Some(multi_value) = args.multi_value {
+        policy.multi_value = multi_value;
+    }
+    if let Some(separator) = args.separator {
+        policy.separator = separator;
+    }
+    if let Some(subgenres) = args.subgenres {
+        policy.subgenres = subgenres;
+    }
+    if let Some(overwrite_label) = args.overwrite_label {
+        policy.overwrite_label = overwrite_label;
+    }
+    if let Some(tag_targets) = args.tag_targets {
+        policy.tag_targets = tag_targets;
+    }
+
+    let cache = if args.no_cache {
+        None
+    } else {
+        Some(Cache::new(
+            Duration::from_secs(args.cache_ttl.saturating_mul(24 * 60 * 60)),
+            args.offline,
+        )?)
     };
 
     let ctx = AppContext {
         mb_client,
         discogs_client,
+        lastfm_client,
+        spotify_client,
+        bandcamp_client,
+        art_client,
+        cache,
+        genre_map: GenreMap::load()?,
+        filter: TagFilter::load()?,
+        policy,
+        path_pattern: args.from_path,
+        min_score: args.min_score,
+        by_track: args.by_track,
+        compilation: args.compilation,
+        discogs_master: args.discogs_master,
+        preference: Preference {
+            country: args.prefer_country,
+            format: args.prefer_format,
+        },
+        journal: args.write.then(Journal::create).transpose()?,
     };
 
-    if !args.files.is_empty() {
+    if let Some(dir) = args.dir {
+        process_library(
+            &ctx,
+            &dir,
+            args.read,
+            args.write,
+            args.jobs.get(),
+            args.resume,
+        )
+        .await?;
+    } else if !args.files.is_empty() {
         for path_str in args.files {
             let path = Path::new(&path_str);
             if !path.exists() {
@@ -88,23 +371,11 @@ async fn main() -> Result<()> {
                 continue;
             }
 
-            let mut files_to_process = Vec::new();
-            if path.is_dir() {
-                // Fix redundant_closure: |e| e.ok() -> Result::ok
-                for entry in WalkDir::new(path).into_iter().filter_map(Result::ok) {
-                    if entry.file_type().is_file()
-                        && entry
-                            .path()
-                            .extension()
-                            .and_then(|s| s.to_str())
-                            .is_some_and(|ext| matches!(ext, "flac" | "mp3" | "ogg" | "m4a"))
-                    {
-                        files_to_process.push(entry.path().to_path_buf());
-                    }
-                }
+            let files_to_process = if path.is_dir() {
+                collect_audio_files(path)
             } else {
-                files_to_process.push(path.to_path_buf());
-            }
+                vec![path.to_path_buf()]
+            };
 
             for file_path in files_to_process {
                 if let Err(e) = process_file(&ctx, &file_path, args.read, args.write).await {
@@ -112,10 +383,17 @@ async fn main() -> Result<()> {
                 }
             }
         }
+    } else if let (Some(artist), Some(title)) = (&args.artist, &args.track) {
+        let result = process_track_query(&ctx, artist, title).await?;
+        print_metadata(&result);
     } else if let (Some(artist), Some(album)) = (args.artist, args.album) {
         let result = process_query(&ctx, &artist, &album).await?;
         print_metadata(&result);
     }
 
+    if let Some(journal) = ctx.journal {
+        journal.finish()?;
+    }
+
     Ok(())
 }
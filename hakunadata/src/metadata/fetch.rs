@@ -1,47 +1,204 @@
+use crate::cache::{NotCached, cached};
 use crate::context::AppContext;
+use crate::fetchers::RateLimited;
+use crate::fetchers::discogs::{DiscogsClient, DiscogsResult};
+use crate::metadata::matching::VARIOUS_ARTISTS;
 use crate::metadata::{genres, labels, subgenres};
+use crate::progress;
 use anyhow::Result;
+use lofty::picture::Picture;
 
+#[derive(Default)]
 pub struct FetchedMetadata {
     pub genres: Vec<String>,
     pub subgenres: Vec<String>,
     pub labels: Vec<String>,
+    pub year: Option<String>,
+    pub original_date: Option<String>,
+    pub country: Option<String>,
+    pub catalog_number: Option<String>,
+    pub release_id: Option<String>,
+    pub release_group_id: Option<String>,
+    /// Only set by track lookups
+    pub recording_id: Option<String>,
+    pub artist_ids: Vec<String>,
+    pub artwork_url: Option<String>,
+    pub artwork: Option<Picture>,
+    /// Match score per source that contributed
+    pub confidence: Vec<(&'static str, f64)>,
+    /// Sources that weren't queried because `--offline` found nothing cached
+    pub uncached: Vec<&'static str>,
 }
 
 pub async fn process_query(ctx: &AppContext, artist: &str, album: &str) -> Result<FetchedMetadata> {
-    // Run fetchers concurrently (or rather, run whichever is enabled)
+    // Preferences change which pressing is picked, so they're part of the cache key
+    let preference = ctx.preference.to_string();
+    let release_key: &[&str] = if preference.is_empty() {
+        &[artist, album]
+    } else {
+        &[artist, album, &preference]
+    };
+
+    // Run fetchers concurrently (or rather, run whichever is enabled). The
+    // Discogs one also gives the result of the master lookup, which is
+    // reported on its own when it isn't cached
     let discogs_future = async {
         if let Some(client) = &ctx.discogs_client {
-            client.fetch_metadata(artist, album).await
+            let release = cached(
+                ctx.cache.as_ref(),
+                "discogs",
+                release_key,
+                Option::is_some,
+                client.fetch_metadata(artist, album, &ctx.preference),
+            )
+            .await;
+
+            match release {
+                Ok(Some(release)) if ctx.discogs_master => {
+                    match apply_master(ctx, client, release).await {
+                        Err(e) if e.is::<NotCached>() => (Ok(None), Err(e)),
+                        release => (release.map(Some), Ok(())),
+                    }
+                }
+                release => (release, Ok(())),
+            }
         } else {
-            Ok(None)
+            (Ok(None), Ok(()))
         }
     };
 
     let mb_future = async {
         if let Some(client) = &ctx.mb_client {
-            client.fetch_genres(artist, album).await
+            cached(
+                ctx.cache.as_ref(),
+                "musicbrainz",
+                release_key,
+                Option::is_some,
+                client.fetch_metadata(artist, album, &ctx.preference),
+            )
+            .await
         } else {
-            Ok(vec![])
+            Ok(None)
         }
     };
 
-    let (discogs_res, mb_res) = tokio::join!(discogs_future, mb_future);
+    let lastfm_future = async {
+        if let Some(client) = &ctx.lastfm_client {
+            cached(
+                ctx.cache.as_ref(),
+                "lastfm",
+                &[artist, album],
+                Option::is_some,
+                client.fetch_genres(artist, album),
+            )
+            .await
+        } else {
+            Ok(None)
+        }
+    };
+
+    let spotify_future = async {
+        if let Some(client) = &ctx.spotify_client {
+            cached(
+                ctx.cache.as_ref(),
+                "spotify",
+                &[artist, album],
+                Option::is_some,
+                client.fetch_genres(artist, album),
+            )
+            .await
+        } else {
+            Ok(None)
+        }
+    };
+
+    let bandcamp_future = async {
+        if let Some(client) = &ctx.bandcamp_client {
+            cached(
+                ctx.cache.as_ref(),
+                "bandcamp",
+                &[artist, album],
+                Option::is_some,
+                client.fetch_metadata(artist, album),
+            )
+            .await
+        } else {
+            Ok(None)
+        }
+    };
+
+    let ((discogs_res, master_res), mb_res, lastfm_res, spotify_res, bandcamp_res) = tokio::join!(
+        discogs_future,
+        mb_future,
+        lastfm_future,
+        spotify_future,
+        bandcamp_future
+    );
+
+    let uncached = [
+        not_cached("discogs", &discogs_res),
+        not_cached("discogs-master", &master_res),
+        not_cached("musicbrainz", &mb_res),
+        not_cached("lastfm", &lastfm_res),
+        not_cached("spotify", &spotify_res),
+        not_cached("bandcamp", &bandcamp_res),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    // A throttled source would only come back empty, so stop instead
+    let throttled = [
+        rate_limited(&discogs_res),
+        rate_limited(&mb_res),
+        rate_limited(&lastfm_res),
+        rate_limited(&spotify_res),
+        rate_limited(&bandcamp_res),
+    ];
+    if let Some(source) = throttled.into_iter().flatten().next() {
+        return Err(RateLimited(source).into());
+    }
 
     let mut genres = std::collections::HashSet::new();
     let mut subgenres = std::collections::HashSet::new();
     let mut labels = std::collections::HashSet::new();
+    let mut discogs_image = None;
+    let mut release_id = None;
+    let mut release_group_id = None;
+    let mut artist_ids = Vec::new();
+    let mut year = None;
+    let mut original_date = None;
+    let mut country = None;
+    let mut catalog_number = None;
+    let mut confidence = Vec::new();
 
     // Process Discogs
-    if let Ok(Some(data)) = discogs_res {
+    if let Ok(Some(data)) = discogs_res
+        && accept(
+            ctx,
+            &mut confidence,
+            "discogs",
+            data.title.as_deref().unwrap_or_default(),
+            data.score,
+        )
+    {
+        discogs_image = data.cover_image.clone();
+        year = data.year.clone().filter(|y| !y.is_empty() && y != "0");
+        country = data.country.clone().filter(|c| !c.is_empty());
+        // Discogs uses "none" for releases without a catalog number
+        catalog_number = data
+            .catno
+            .clone()
+            .filter(|c| !c.is_empty() && !c.eq_ignore_ascii_case("none"));
+
         let g = genres::process(&data);
         for item in g {
-            genres.insert(item);
+            genres.insert(ctx.genre_map.canonicalize(&item));
         }
 
         let s = subgenres::process(&data);
         for item in s {
-            subgenres.insert(item);
+            subgenres.insert(ctx.genre_map.canonicalize(&item));
         }
 
         let l = labels::process(&data);
@@ -51,24 +208,366 @@ pub async fn process_query(ctx: &AppContext, artist: &str, album: &str) -> Resul
     }
 
     // Process MusicBrainz
-    if let Ok(mb_genres) = mb_res {
-        for g in mb_genres {
-            genres.insert(g);
+    if let Ok(Some(data)) = mb_res
+        && accept(
+            ctx,
+            &mut confidence,
+            "musicbrainz",
+            &data.matched,
+            data.score,
+        )
+    {
+        for g in data.genres {
+            genres.insert(ctx.genre_map.canonicalize(&g));
+        }
+        release_id = Some(data.release_id);
+        release_group_id = data.release_group_id;
+        artist_ids = data.artist_ids;
+
+        // MusicBrainz dates are more precise, prefer them over Discogs
+        if let Some(date) = data.date {
+            year = Some(date.chars().take(4).collect());
+        }
+        original_date = data.first_release_date;
+        country = data.country.or(country);
+        catalog_number = data.catalog_number.or(catalog_number);
+    }
+
+    // Process Bandcamp, for releases the databases above don't know
+    if let Ok(Some(data)) = bandcamp_res
+        && accept(ctx, &mut confidence, "bandcamp", &data.matched, data.score)
+    {
+        for g in data.genres {
+            genres.insert(ctx.genre_map.canonicalize(&g));
+        }
+        if labels.is_empty()
+            && let Some(label) = data.label
+        {
+            labels.insert(label);
+        }
+        year = year.or(data.year);
+    }
+
+    // Process Last.fm and Spotify, which only provide genres
+    for (source, res) in [("lastfm", lastfm_res), ("spotify", spotify_res)] {
+        let Ok(Some(data)) = res else {
+            continue;
+        };
+        if !accept(ctx, &mut confidence, source, &data.matched, data.score) {
+            continue;
+        }
+        for g in data.genres {
+            genres.insert(ctx.genre_map.canonicalize(&g));
         }
     }
 
     let mut sorted_genres: Vec<_> = genres.into_iter().collect();
+    sorted_genres.retain(|g| ctx.filter.allows_genre(g));
     sorted_genres.sort();
 
     let mut sorted_subgenres: Vec<_> = subgenres.into_iter().collect();
+    sorted_subgenres.retain(|g| ctx.filter.allows_genre(g));
     sorted_subgenres.sort();
 
     let mut sorted_labels: Vec<_> = labels.into_iter().collect();
+    sorted_labels.retain(|l| ctx.filter.allows_label(l));
     sorted_labels.sort();
 
+    // Fetch the front cover last, it needs the IDs resolved above
+    let (artwork_url, artwork) = if let Some(client) = &ctx.art_client {
+        let urls = client.cover_urls(
+            release_id.as_deref(),
+            release_group_id.as_deref(),
+            discogs_image.as_deref(),
+        );
+        match client.fetch(&urls).await {
+            Ok(Some((url, picture))) => (Some(url), Some(picture)),
+            Ok(None) => (None, None),
+            Err(e) => {
+                progress::eprintln(format!("Warning: Failed to fetch artwork: {e:?}"));
+                (None, None)
+            }
+        }
+    } else {
+        (None, None)
+    };
+
     Ok(FetchedMetadata {
         genres: sorted_genres,
         subgenres: sorted_subgenres,
         labels: sorted_labels,
+        year,
+        original_date,
+        country,
+        catalog_number,
+        release_id,
+        release_group_id,
+        recording_id: None,
+        artist_ids,
+        artwork_url,
+        artwork,
+        confidence,
+        uncached,
     })
 }
+
+/// Look up a single recording instead of its album, for singles and compilation tracks
+///
+/// Discogs and Bandcamp are searched by release, so just MusicBrainz, Last.fm
+/// and Spotify are queried.
+pub async fn process_track_query(
+    ctx: &AppContext,
+    artist: &str,
+    title: &str,
+) -> Result<FetchedMetadata> {
+    let mb_future = async {
+        if let Some(client) = &ctx.mb_client {
+            cached(
+                ctx.cache.as_ref(),
+                "musicbrainz-recording",
+                &[artist, title],
+                Option::is_some,
+                client.fetch_recording(artist, title),
+            )
+            .await
+        } else {
+            Ok(None)
+        }
+    };
+
+    let lastfm_future = async {
+        if let Some(client) = &ctx.lastfm_client {
+            cached(
+                ctx.cache.as_ref(),
+                "lastfm-track",
+                &[artist, title],
+                Option::is_some,
+                client.fetch_track_genres(artist, title),
+            )
+            .await
+        } else {
+            Ok(None)
+        }
+    };
+
+    let spotify_future = async {
+        if let Some(client) = &ctx.spotify_client {
+            cached(
+                ctx.cache.as_ref(),
+                "spotify-track",
+                &[artist, title],
+                Option::is_some,
+                client.fetch_track_genres(artist, title),
+            )
+            .await
+        } else {
+            Ok(None)
+        }
+    };
+
+    let (mb_res, lastfm_res, spotify_res) = tokio::join!(mb_future, lastfm_future, spotify_future);
+
+    let uncached = [
+        not_cached("musicbrainz", &mb_res),
+        not_cached("lastfm", &lastfm_res),
+        not_cached("spotify", &spotify_res),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let throttled = [
+        rate_limited(&mb_res),
+        rate_limited(&lastfm_res),
+        rate_limited(&spotify_res),
+    ];
+    if let Some(source) = throttled.into_iter().flatten().next() {
+        return Err(RateLimited(source).into());
+    }
+
+    let mut genres = std::collections::HashSet::new();
+    let mut recording_id = None;
+    let mut artist_ids = Vec::new();
+    let mut year = None;
+    let mut original_date = None;
+    let mut confidence = Vec::new();
+
+    if let Ok(Some(data)) = mb_res
+        && accept(
+            ctx,
+            &mut confidence,
+            "musicbrainz",
+            &data.matched,
+            data.score,
+        )
+    {
+        for g in data.genres {
+            genres.insert(ctx.genre_map.canonicalize(&g));
+        }
+        recording_id = Some(data.recording_id);
+        artist_ids = data.artist_ids;
+        year = data
+            .first_release_date
+            .as_ref()
+            .map(|date| date.chars().take(4).collect());
+        original_date = data.first_release_date;
+    }
+
+    for (source, res) in [("lastfm", lastfm_res), ("spotify", spotify_res)] {
+        let Ok(Some(data)) = res else {
+            continue;
+        };
+        if !accept(ctx, &mut confidence, source, &data.matched, data.score) {
+            continue;
+        }
+        for g in data.genres {
+            genres.insert(ctx.genre_map.canonicalize(&g));
+        }
+    }
+
+    let mut sorted_genres: Vec<_> = genres.into_iter().collect();
+    sorted_genres.retain(|g| ctx.filter.allows_genre(g));
+    sorted_genres.sort();
+
+    Ok(FetchedMetadata {
+        genres: sorted_genres,
+        subgenres: vec![],
+        labels: vec![],
+        year,
+        original_date,
+        country: None,
+        catalog_number: None,
+        release_id: None,
+        release_group_id: None,
+        recording_id,
+        artist_ids,
+        artwork_url: None,
+        artwork: None,
+        confidence,
+        uncached,
+    })
+}
+
+/// Look up a compilation track: genres of the recording, release details of
+/// the compilation as credited to various artists
+pub async fn process_compilation_track(
+    ctx: &AppContext,
+    artist: &str,
+    album: &str,
+    title: &str,
+) -> Result<FetchedMetadata> {
+    let (track, compilation) = tokio::join!(
+        process_track_query(ctx, artist, title),
+        process_query(ctx, VARIOUS_ARTISTS, album)
+    );
+    Ok(merge_compilation(track?, &compilation?))
+}
+
+/// Combine a track lookup with the lookup of the compilation it's on
+///
+/// Album-level genres don't fit the individual tracks, so only label, date,
+/// country, catalog number, release IDs and cover are taken from the album.
+pub fn merge_compilation(track: FetchedMetadata, album: &FetchedMetadata) -> FetchedMetadata {
+    let mut confidence = track.confidence;
+    confidence.extend(&album.confidence);
+    let mut uncached = track.uncached;
+    for source in &album.uncached {
+        if !uncached.contains(source) {
+            uncached.push(source);
+        }
+    }
+
+    FetchedMetadata {
+        genres: track.genres,
+        subgenres: track.subgenres,
+        labels: album.labels.clone(),
+        year: album.year.clone().or(track.year),
+        original_date: track.original_date,
+        country: album.country.clone(),
+        catalog_number: album.catalog_number.clone(),
+        release_id: album.release_id.clone(),
+        release_group_id: album.release_group_id.clone(),
+        recording_id: track.recording_id,
+        artist_ids: track.artist_ids,
+        artwork_url: album.artwork_url.clone(),
+        artwork: album.artwork.clone(),
+        confidence,
+        uncached,
+    }
+}
+
+/// Replace genres, styles and year of a Discogs release with those of its master
+///
+/// Label and catalog number differ between pressings and stay those of the release.
+/// Going over the rate limit or a master missing from the cache fails, so the
+/// pressing's data isn't written instead.
+async fn apply_master(
+    ctx: &AppContext,
+    client: &DiscogsClient,
+    mut release: DiscogsResult,
+) -> Result<DiscogsResult> {
+    let Some(id) = release.master_id.filter(|id| *id != 0) else {
+        return Ok(release);
+    };
+
+    let master = cached(
+        ctx.cache.as_ref(),
+        "discogs-master",
+        &[&id.to_string()],
+        Option::is_some,
+        client.fetch_master(id),
+    )
+    .await;
+
+    match master {
+        Ok(Some(master)) => {
+            if master.genres.as_ref().is_some_and(|g| !g.is_empty()) {
+                release.genre = master.genres;
+            }
+            if master.styles.as_ref().is_some_and(|s| !s.is_empty()) {
+                release.style = master.styles;
+            }
+            if let Some(year) = master.year.filter(|year| *year != 0) {
+                release.year = Some(year.to_string());
+            }
+        }
+        Ok(None) => {}
+        Err(e) if e.is::<NotCached>() || e.is::<RateLimited>() => return Err(e),
+        Err(e) => progress::eprintln(format!(
+            "Warning: Failed to fetch Discogs master {id}: {e:?}"
+        )),
+    }
+
+    Ok(release)
+}
+
+/// `source` if its lookup failed for not being cached
+fn not_cached<T>(source: &'static str, res: &Result<T>) -> Option<&'static str> {
+    matches!(res, Err(e) if e.is::<NotCached>()).then_some(source)
+}
+
+/// The source that turned the lookup down for going over its rate limit
+fn rate_limited<T>(res: &Result<T>) -> Option<&'static str> {
+    let e = res.as_ref().err()?;
+    e.downcast_ref::<RateLimited>().map(|limited| limited.0)
+}
+
+/// Keep a match only if it's close enough to the query, recording its score
+fn accept(
+    ctx: &AppContext,
+    confidence: &mut Vec<(&'static str, f64)>,
+    source: &'static str,
+    matched: &str,
+    score: f64,
+) -> bool {
+    if score < ctx.min_score {
+        progress::eprintln(format!(
+            "Rejected {source} match '{matched}' (score {score:.2} below {:.2})",
+            ctx.min_score
+        ));
+        return false;
+    }
+
+    confidence.push((source, score));
+    true
+}
@@ -0,0 +1,153 @@
+use crate::config;
+use crate::metadata::genres::normalize;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use toml_edit::Item;
+
+/// User tags MusicBrainz and Last.fm return that aren't genres
+const JUNK_GENRES: &[&str] = &[
+    "seen live",
+    "favorites",
+    "favourites",
+    "favorite albums",
+    "favourite albums",
+    "albums i own",
+    "owned",
+    "my albums",
+    "love",
+    "awesome",
+];
+
+/// Genres and labels to drop from fetched metadata
+pub struct TagFilter {
+    genre_blacklist: HashSet<String>,
+    /// Only these genres are kept, if set
+    genre_whitelist: Option<HashSet<String>>,
+    label_blacklist: HashSet<String>,
+    label_whitelist: Option<HashSet<String>>,
+    /// Drop the built-in junk tags, decade tags and "Not On Label"
+    default_blacklist: bool,
+}
+
+impl Default for TagFilter {
+    fn default() -> Self {
+        Self {
+            genre_blacklist: HashSet::new(),
+            genre_whitelist: None,
+            label_blacklist: HashSet::new(),
+            label_whitelist: None,
+            default_blacklist: true,
+        }
+    }
+}
+
+impl TagFilter {
+    /// Defaults extended by the `[filter]` table of `config.toml`
+    pub fn load() -> Result<Self> {
+        let mut filter = Self::default();
+
+        let Some((path, doc)) = config::load("config.toml")? else {
+            return Ok(filter);
+        };
+        let Some(table) = doc.get("filter").and_then(|item| item.as_table_like()) else {
+            return Ok(filter);
+        };
+
+        let context = || format!("Invalid [filter] table in {}", path.display());
+
+        for (key, item) in table.iter() {
+            match key {
+                "genre_blacklist" => {
+                    filter.genre_blacklist = parse_list(key, item).with_context(context)?
+                }
+                "genre_whitelist" => {
+                    filter.genre_whitelist = Some(parse_list(key, item).with_context(context)?)
+                }
+                "label_blacklist" => {
+                    filter.label_blacklist = parse_list(key, item).with_context(context)?
+                }
+                "label_whitelist" => {
+                    filter.label_whitelist = Some(parse_list(key, item).with_context(context)?)
+                }
+                "default_blacklist" => {
+                    filter.default_blacklist = item
+                        .as_bool()
+                        .with_context(|| format!("'{key}' must be a boolean"))
+                        .with_context(context)?;
+                }
+                _ => return Err(anyhow::anyhow!("Unknown key '{key}'").context(context())),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    /// Whether a genre (or subgenre) should be kept
+    pub fn allows_genre(&self, genre: &str) -> bool {
+        let normalized = normalize(genre);
+
+        if self.default_blacklist && (is_junk_genre(&normalized) || is_decade(&normalized)) {
+            return false;
+        }
+
+        allowed(
+            &normalized,
+            &self.genre_blacklist,
+            self.genre_whitelist.as_ref(),
+        )
+    }
+
+    /// Whether a label should be kept
+    pub fn allows_label(&self, label: &str) -> bool {
+        let normalized = normalize(label);
+
+        // Discogs credits self-released records to "Not On Label (Artist Self-released)"
+        if self.default_blacklist && normalized.starts_with("notonlabel") {
+            return false;
+        }
+
+        allowed(
+            &normalized,
+            &self.label_blacklist,
+            self.label_whitelist.as_ref(),
+        )
+    }
+}
+
+fn allowed(
+    normalized: &str,
+    blacklist: &HashSet<String>,
+    whitelist: Option<&HashSet<String>>,
+) -> bool {
+    !blacklist.contains(normalized) && whitelist.is_none_or(|list| list.contains(normalized))
+}
+
+fn is_junk_genre(normalized: &str) -> bool {
+    JUNK_GENRES.iter().any(|junk| normalize(junk) == normalized)
+}
+
+/// Decade tags like "80s", "90's" or "1970s"
+fn is_decade(normalized: &str) -> bool {
+    normalized.strip_suffix('s').is_some_and(|digits| {
+        matches!(digits.len(), 2 | 4)
+            && digits.ends_with('0')
+            && digits.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+/// Parse an array of strings into their normalized forms
+fn parse_list(key: &str, item: &Item) -> Result<HashSet<String>> {
+    let values = item
+        .as_array()
+        .with_context(|| format!("'{key}' must be an array"))?;
+
+    values
+        .iter()
+        .map(|value| {
+            value
+                .as_str()
+                .map(normalize)
+                .with_context(|| format!("'{key}' must only contain strings"))
+        })
+        .collect()
+}
@@ -1,5 +1,101 @@
+use crate::config;
 use crate::fetchers::discogs::DiscogsResult;
-use std::collections::HashSet;
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Spellings that different sources use for the same genre, keyed by their normalized form
+const CANONICAL_GENRES: &[(&str, &str)] = &[
+    ("hiphop", "Hip Hop"),
+    ("rap", "Hip Hop"),
+    ("rb", "R&B"),
+    ("rnb", "R&B"),
+    ("rhythmandblues", "R&B"),
+    ("drumnbass", "Drum n Bass"),
+    ("drumandbass", "Drum n Bass"),
+    ("dnb", "Drum n Bass"),
+    ("triphop", "Trip Hop"),
+    ("idm", "IDM"),
+    ("edm", "EDM"),
+    ("ukgarage", "UK Garage"),
+    ("synthpop", "Synth-pop"),
+    ("postpunk", "Post-Punk"),
+    ("postrock", "Post Rock"),
+    ("lofi", "Lo-Fi"),
+    ("funksoul", "Funk / Soul"),
+    ("funkandsoul", "Funk / Soul"),
+    ("folkworldcountry", "Folk, World, & Country"),
+];
+
+/// Mapping from genre spellings to the canonical name written to tags
+pub struct GenreMap {
+    canonical: HashMap<String, String>,
+}
+
+impl GenreMap {
+    /// Built-in mapping extended with `genre-map.toml` from the config directory
+    ///
+    /// The file maps aliases to canonical names, e.g. `"hip-hop" = "Hip Hop"`.
+    pub fn load() -> Result<Self> {
+        let mut map = Self::builtin();
+
+        if let Some((path, doc)) = config::load("genre-map.toml")? {
+            for (alias, item) in doc.iter() {
+                let canonical = item.as_str().with_context(|| {
+                    format!("{}: value for '{alias}' must be a string", path.display())
+                })?;
+                map.canonical
+                    .insert(normalize(alias), canonical.to_string());
+            }
+        }
+
+        Ok(map)
+    }
+
+    fn builtin() -> Self {
+        Self {
+            canonical: CANONICAL_GENRES
+                .iter()
+                .map(|(alias, canonical)| ((*alias).to_string(), (*canonical).to_string()))
+                .collect(),
+        }
+    }
+
+    /// Return the canonical spelling of a genre
+    ///
+    /// Unknown genres keep their casing, except all-lowercase tags (common on
+    /// MusicBrainz and Last.fm) which are title-cased.
+    pub fn canonicalize(&self, genre: &str) -> String {
+        let genre = genre.trim();
+
+        if let Some(canonical) = self.canonical.get(&normalize(genre)) {
+            return canonical.clone();
+        }
+
+        if genre.chars().any(char::is_uppercase) {
+            return genre.to_string();
+        }
+
+        genre
+            .split_whitespace()
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_uppercase().chain(chars).collect()
+                })
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Lowercase a genre and drop everything but letters and digits
+pub fn normalize(genre: &str) -> String {
+    genre
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
 
 pub fn process(result: &DiscogsResult) -> Vec<String> {
     let mut genres = HashSet::<String>::new();
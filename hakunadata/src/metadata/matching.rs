@@ -0,0 +1,173 @@
+use clap::ValueEnum;
+use std::fmt;
+
+/// Album artist of compilations, as MusicBrainz credits them
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// Whether an artist stands for the many artists of a compilation
+///
+/// Discogs credits these to "Various", taggers use "VA" and the like.
+pub fn is_various(artist: &str) -> bool {
+    matches!(
+        artist.trim().to_lowercase().as_str(),
+        "various artists" | "various" | "va" | "v.a." | "v/a"
+    )
+}
+
+/// Similarity between a query and a candidate release, from 0.0 to 1.0
+///
+/// Artist and album are compared separately and averaged, after lowercasing,
+/// dropping punctuation and bracketed suffixes like "(Remastered)".
+pub fn score(artist: &str, album: &str, candidate_artist: &str, candidate_album: &str) -> f64 {
+    let artist_score = if is_various(artist) && is_various(candidate_artist) {
+        1.0
+    } else {
+        strsim::normalized_levenshtein(&normalize(artist), &normalize(candidate_artist))
+    };
+    let album_score =
+        strsim::normalized_levenshtein(&normalize(album), &normalize(candidate_album));
+
+    f64::midpoint(artist_score, album_score)
+}
+
+/// Pick the candidate with the highest score
+pub fn best<T>(candidates: impl IntoIterator<Item = (T, f64)>) -> Option<(T, f64)> {
+    candidates
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+/// Pick the candidate with the highest score, with the ones that fit the
+/// preferred pressing ranked up by `bonus`
+///
+/// The returned score is the plain match score, the bonus only reorders.
+pub fn best_preferred<T>(candidates: impl IntoIterator<Item = (T, f64, f64)>) -> Option<(T, f64)> {
+    candidates
+        .into_iter()
+        .max_by(|(_, a, a_bonus), (_, b, b_bonus)| (a + a_bonus).total_cmp(&(b + b_bonus)))
+        .map(|(candidate, score, _)| (candidate, score))
+}
+
+/// Release format preferred with `--prefer-format`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Vinyl,
+    Cd,
+    Cassette,
+    Digital,
+}
+
+impl Format {
+    /// Whether a format as named by Discogs ("Vinyl", "File") or MusicBrainz
+    /// ("12\" Vinyl", "Digital Media") is this one
+    fn matches(self, format: &str) -> bool {
+        let format = format.to_lowercase();
+        match self {
+            Self::Vinyl => format.contains("vinyl"),
+            Self::Cd => format.split([' ', '-']).any(|word| word == "cd"),
+            Self::Cassette => format.contains("cassette"),
+            Self::Digital => format == "file" || format.contains("digital"),
+        }
+    }
+}
+
+/// Rank bonus per preference a candidate meets, enough to settle ties between
+/// pressings of the same album without outranking a clearly better match
+const PREFERENCE_BONUS: f64 = 0.05;
+
+/// Country and format of the pressing to prefer among equally good matches
+#[derive(Debug, Default)]
+pub struct Preference {
+    pub country: Option<String>,
+    pub format: Option<Format>,
+}
+
+impl Preference {
+    pub fn is_empty(&self) -> bool {
+        self.country.is_none() && self.format.is_none()
+    }
+
+    /// Rank bonus of a candidate released in `country` on `formats`
+    pub fn bonus<'a>(
+        &self,
+        country: Option<&str>,
+        formats: impl IntoIterator<Item = &'a str>,
+    ) -> f64 {
+        let mut bonus = 0.0;
+        if let (Some(preferred), Some(country)) = (&self.country, country)
+            && country_code(preferred) == country_code(country)
+        {
+            bonus += PREFERENCE_BONUS;
+        }
+        if let Some(preferred) = self.format
+            && formats.into_iter().any(|format| preferred.matches(format))
+        {
+            bonus += PREFERENCE_BONUS;
+        }
+        bonus
+    }
+}
+
+/// Cache key part, so candidates picked under other preferences aren't reused
+impl fmt::Display for Preference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(country) = &self.country {
+            parts.push(format!("country={}", country_code(country)));
+        }
+        if let Some(format) = self.format {
+            parts.push(format!("format={format:?}"));
+        }
+        f.write_str(&parts.join(","))
+    }
+}
+
+/// Discogs names countries ("UK", "Germany") where MusicBrainz uses ISO codes ("GB", "DE")
+const COUNTRY_NAMES: &[(&str, &str)] = &[
+    ("GB", "UK"),
+    ("GB", "United Kingdom"),
+    ("US", "USA"),
+    ("DE", "Germany"),
+    ("FR", "France"),
+    ("NL", "Netherlands"),
+    ("BE", "Belgium"),
+    ("IT", "Italy"),
+    ("ES", "Spain"),
+    ("SE", "Sweden"),
+    ("JP", "Japan"),
+    ("CA", "Canada"),
+    ("AU", "Australia"),
+    ("XE", "Europe"),
+    ("XW", "Worldwide"),
+];
+
+/// ISO code of a country given by name or code, uppercased
+fn country_code(country: &str) -> String {
+    let country = country.trim();
+    COUNTRY_NAMES
+        .iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(country))
+        .map_or_else(|| country.to_uppercase(), |(code, _)| (*code).to_string())
+}
+
+fn normalize(value: &str) -> String {
+    let mut result = String::new();
+    let mut depth = 0usize;
+
+    for c in value.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            c if c.is_alphanumeric() => result.extend(c.to_lowercase()),
+            _ => result.push(' '),
+        }
+    }
+
+    // Keep the original text if it was nothing but a bracketed title
+    if result.trim().is_empty() {
+        return value.to_lowercase();
+    }
+
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -1,4 +1,6 @@
 pub mod fetch;
+pub mod filter;
 pub mod genres;
 pub mod labels;
+pub mod matching;
 pub mod subgenres;
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Part of a path component pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Artist,
+    Album,
+    /// Track title, matched but not used for lookups
+    Track,
+    /// `*`, matches anything
+    Skip,
+    Literal(String),
+}
+
+/// Pattern like `Artist/Album/Track` or `Artist - Album/*` matched against the
+/// last components of a file path (the file name without its extension included)
+#[derive(Clone, Debug)]
+pub struct PathPattern {
+    components: Vec<Vec<Token>>,
+}
+
+impl FromStr for PathPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        let components: Vec<Vec<Token>> =
+            pattern.trim_matches('/').split('/').map(tokenize).collect();
+
+        let has = |wanted: &Token| components.iter().flatten().any(|t| t == wanted);
+        if !has(&Token::Artist) || !has(&Token::Album) {
+            anyhow::bail!("Pattern must contain both Artist and Album: {pattern}");
+        }
+
+        Ok(Self { components })
+    }
+}
+
+impl PathPattern {
+    /// Extract artist and album from a path, if it matches the pattern
+    pub fn parse(&self, path: &Path) -> Option<(String, String)> {
+        let mut names = path_names(path);
+        if names.len() < self.components.len() {
+            return None;
+        }
+        let names = names.split_off(names.len() - self.components.len());
+
+        let mut artist = None;
+        let mut album = None;
+
+        for (tokens, name) in self.components.iter().zip(&names) {
+            for (token, value) in match_component(tokens, name)? {
+                match token {
+                    Token::Artist => artist = Some(value),
+                    Token::Album => album = Some(value),
+                    _ => {}
+                }
+            }
+        }
+
+        Some((artist?, album?))
+    }
+}
+
+/// Guess artist and album from the usual `Artist/Album/track` or `Artist - Album/track` layouts
+pub fn guess(path: &Path) -> Option<(String, String)> {
+    let album_dir = path.parent()?;
+    let album_name = album_dir.file_name()?.to_str()?.trim();
+
+    if let Some((artist, album)) = album_name.split_once(" - ") {
+        let (artist, album) = (artist.trim(), album.trim());
+        if !artist.is_empty() && !album.is_empty() {
+            return Some((artist.to_string(), album.to_string()));
+        }
+    }
+
+    let artist_name = album_dir.parent()?.file_name()?.to_str()?.trim();
+    if artist_name.is_empty() || album_name.is_empty() {
+        return None;
+    }
+
+    Some((artist_name.to_string(), album_name.to_string()))
+}
+
+/// Path components as strings, with the extension stripped from the file name
+fn path_names(path: &Path) -> Vec<String> {
+    let mut names: Vec<String> = path
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.iter())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+
+    if let Some(stem) = path.file_stem() {
+        names.push(stem.to_string_lossy().into_owned());
+    }
+
+    names
+}
+
+/// Split a pattern component into fields and the literal text between them
+fn tokenize(component: &str) -> Vec<Token> {
+    const FIELDS: &[(&str, Token)] = &[
+        ("Artist", Token::Artist),
+        ("Album", Token::Album),
+        ("Track", Token::Track),
+        ("*", Token::Skip),
+    ];
+
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut rest = component;
+
+    'outer: while !rest.is_empty() {
+        for (name, token) in FIELDS {
+            if let Some(after) = rest.strip_prefix(name) {
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                }
+                tokens.push(token.clone());
+                rest = after;
+                continue 'outer;
+            }
+        }
+
+        let mut chars = rest.chars();
+        literal.extend(chars.next());
+        rest = chars.as_str();
+    }
+
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+
+    tokens
+}
+
+/// Match a single path component, returning the captured field values
+fn match_component(tokens: &[Token], name: &str) -> Option<Vec<(Token, String)>> {
+    let mut captures = Vec::new();
+    let mut rest = name;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let Token::Literal(literal) = token {
+            rest = rest.strip_prefix(literal.as_str())?;
+            continue;
+        }
+
+        // A field runs until the next literal, or to the end of the component
+        let end = match tokens.get(i + 1) {
+            Some(Token::Literal(next)) => rest.find(next.as_str())?,
+            _ => rest.len(),
+        };
+
+        let value = rest[..end].trim();
+        if value.is_empty() {
+            return None;
+        }
+        captures.push((token.clone(), value.to_string()));
+        rest = &rest[end..];
+    }
+
+    rest.is_empty().then_some(captures)
+}
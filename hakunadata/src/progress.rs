@@ -0,0 +1,69 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt::Display;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Progress of a library run, drawn on stderr when it's a terminal
+struct Bar {
+    bar: ProgressBar,
+    failed: usize,
+}
+
+/// The bar when one is shown, output goes through it to keep it at the bottom
+static BAR: Mutex<Option<Bar>> = Mutex::new(None);
+
+fn bar() -> MutexGuard<'static, Option<Bar>> {
+    BAR.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Show a bar for `total` albums
+pub fn start(total: usize) {
+    let bar = ProgressBar::new(total as u64);
+    bar.set_style(
+        ProgressStyle::with_template("[{bar:30}] {pos}/{len} albums{msg}")
+            .expect("template is valid")
+            .progress_chars("#-"),
+    );
+    *self::bar() = Some(Bar { bar, failed: 0 });
+}
+
+/// Count an album as processed
+pub fn inc(failed: bool) {
+    if let Some(bar) = bar().as_mut() {
+        if failed {
+            bar.failed += 1;
+            bar.bar
+                .set_message(format!(", {} without a full match", bar.failed));
+        }
+        bar.bar.inc(1);
+    }
+}
+
+/// Remove the bar
+pub fn finish() {
+    if let Some(bar) = bar().take() {
+        bar.bar.finish_and_clear();
+    }
+}
+
+/// Run `print` with the bar cleared, then draw it again below the output
+fn suspend(print: impl FnOnce()) {
+    match bar().as_ref() {
+        Some(bar) => bar.bar.suspend(print),
+        None => print(),
+    }
+}
+
+/// `print!` that keeps the bar intact
+pub fn print(text: impl Display) {
+    suspend(|| print!("{text}"));
+}
+
+/// `println!` that keeps the bar intact
+pub fn println(line: impl Display) {
+    suspend(|| println!("{line}"));
+}
+
+/// `eprintln!` that keeps the bar intact
+pub fn eprintln(line: impl Display) {
+    suspend(|| eprintln!("{line}"));
+}
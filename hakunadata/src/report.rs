@@ -0,0 +1,189 @@
+use crate::library::{Album, collect_audio_files, group_albums};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Tag coverage of a library, from the tags already in the files
+#[derive(Serialize)]
+struct Report {
+    albums: usize,
+    tracks: usize,
+    missing_genre: Vec<String>,
+    missing_label: Vec<String>,
+    /// Number of albums per genre, most common first
+    genres: Vec<(String, usize)>,
+    /// Number of albums per label, most common first
+    labels: Vec<(String, usize)>,
+    inconsistent: Vec<Inconsistency>,
+}
+
+/// Field whose value differs between tracks of the same album
+#[derive(Serialize)]
+struct Inconsistency {
+    album: String,
+    field: &'static str,
+    values: Vec<String>,
+}
+
+/// Summarize the genres and labels of a library without fetching anything
+///
+/// Genre strings are split on `separator`, so joined and multi-value genres count the same.
+pub fn report_library(root: &Path, separator: &str, json: Option<&Path>) -> Result<()> {
+    if !root.is_dir() {
+        return Err(anyhow::anyhow!("Not a directory: {}", root.display()));
+    }
+
+    let files = collect_audio_files(root);
+    let albums = group_albums(&files, None, false, None);
+    let report = build_report(&albums, separator);
+
+    if let Some(path) = json {
+        let content = serde_json::to_string_pretty(&report)?;
+        std::fs::write(path, content + "\n")
+            .with_context(|| format!("Failed to write report: {}", path.display()))?;
+        println!("Report written to {}", path.display());
+        return Ok(());
+    }
+
+    println!(
+        "Scanned {} albums ({} files) in {}",
+        report.albums,
+        report.tracks,
+        root.display()
+    );
+
+    print_list("Albums missing genre", &report.missing_genre);
+    print_list("Albums missing label", &report.missing_label);
+    print_counts("Genres", &report.genres);
+    print_counts("Labels", &report.labels);
+
+    let inconsistent: Vec<String> = report
+        .inconsistent
+        .iter()
+        .map(|i| format!("{}: {} ({})", i.album, i.field, i.values.join(" | ")))
+        .collect();
+    print_list("Albums with differing tags", &inconsistent);
+
+    Ok(())
+}
+
+fn build_report(albums: &[Album], separator: &str) -> Report {
+    let mut report = Report {
+        albums: albums.len(),
+        tracks: albums.iter().map(|a| a.tracks.len()).sum(),
+        missing_genre: Vec::new(),
+        missing_label: Vec::new(),
+        genres: Vec::new(),
+        labels: Vec::new(),
+        inconsistent: Vec::new(),
+    };
+
+    let mut genre_counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut label_counts: BTreeMap<String, usize> = BTreeMap::new();
+
+    for album in albums {
+        let name = format!("{} - {}", album.artist, album.album);
+
+        // Genres and label of every track, as sets so their order doesn't matter
+        let track_genres: Vec<BTreeSet<String>> = album
+            .tracks
+            .iter()
+            .map(|track| {
+                track
+                    .genres
+                    .iter()
+                    .flat_map(|genre| genre.split(separator))
+                    .map(str::trim)
+                    .filter(|genre| !genre.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect();
+        let track_labels: Vec<Option<&str>> =
+            album.tracks.iter().map(|t| t.label.as_deref()).collect();
+
+        let album_genres: BTreeSet<&String> = track_genres.iter().flatten().collect();
+        let album_labels: BTreeSet<&str> = track_labels.iter().flatten().copied().collect();
+
+        if album_genres.is_empty() {
+            report.missing_genre.push(name.clone());
+        }
+        if album_labels.is_empty() {
+            report.missing_label.push(name.clone());
+        }
+
+        for genre in album_genres {
+            *genre_counts.entry(genre.clone()).or_default() += 1;
+        }
+        for label in album_labels {
+            *label_counts.entry(label.to_string()).or_default() += 1;
+        }
+
+        let genre_variants: BTreeSet<String> = track_genres
+            .iter()
+            .map(|genres| join_or_none(genres.iter().map(String::as_str), separator))
+            .collect();
+        if genre_variants.len() > 1 {
+            report.inconsistent.push(Inconsistency {
+                album: name.clone(),
+                field: "genre",
+                values: genre_variants.into_iter().collect(),
+            });
+        }
+
+        let label_variants: BTreeSet<String> = track_labels
+            .iter()
+            .map(|label| join_or_none(label.iter().copied(), separator))
+            .collect();
+        if label_variants.len() > 1 {
+            report.inconsistent.push(Inconsistency {
+                album: name,
+                field: "label",
+                values: label_variants.into_iter().collect(),
+            });
+        }
+    }
+
+    report.genres = sorted_counts(genre_counts);
+    report.labels = sorted_counts(label_counts);
+    report
+}
+
+fn join_or_none<'a>(values: impl Iterator<Item = &'a str>, separator: &str) -> String {
+    let joined = values.collect::<Vec<_>>().join(separator);
+    if joined.is_empty() {
+        String::from("(none)")
+    } else {
+        joined
+    }
+}
+
+/// Most common first, alphabetically among equal counts
+fn sorted_counts(counts: BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+    counts
+}
+
+fn print_list(title: &str, items: &[String]) {
+    println!();
+    println!("{title}: {}", items.len());
+    for item in items {
+        println!("  {item}");
+    }
+}
+
+fn print_counts(title: &str, counts: &[(String, usize)]) {
+    println!();
+    println!("{title}: {}", counts.len());
+
+    let width = counts
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (name, count) in counts {
+        println!("  {name:<width$}  {count}");
+    }
+}
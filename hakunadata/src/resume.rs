@@ -0,0 +1,92 @@
+use crate::cache::fnv1a;
+use crate::config;
+use crate::library::Album;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Albums a `--dir --write` run has finished, so `--resume` can skip them
+/// after an interrupted run
+///
+/// Kept under `$XDG_DATA_HOME/hakunadata/resume`, one file per library with
+/// a JSON string per album.
+pub struct ResumeState {
+    path: PathBuf,
+    done: HashSet<String>,
+    file: Mutex<File>,
+}
+
+impl ResumeState {
+    /// Open the state of the library at `root`, starting over unless `resume` is set
+    pub fn open(root: &Path, resume: bool) -> Result<Self> {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let dir = config::data_path("resume")?;
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create resume directory: {}", dir.display()))?;
+        let path = dir.join(format!("{:016x}.jsonl", fnv1a(&root.to_string_lossy())));
+
+        let mut done = HashSet::new();
+        if path.exists() {
+            if resume {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                done = content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect();
+            } else {
+                println!("Starting over, pass --resume to skip the albums of the interrupted run");
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        if !resume {
+            file.set_len(0)?;
+        }
+
+        Ok(Self {
+            path,
+            done,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Whether an earlier run finished the album with this [`key`]
+    pub fn is_done(&self, key: &str) -> bool {
+        self.done.contains(key)
+    }
+
+    /// Remember that the album with this [`key`] is finished
+    pub fn record(&self, key: &str) -> Result<()> {
+        let mut line = serde_json::to_string(key)?;
+        line.push('\n');
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Resume state lock poisoned"))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    /// Drop the state once a run got through the whole library
+    pub fn finish(self) -> Result<()> {
+        fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove {}", self.path.display()))
+    }
+}
+
+/// What identifies an album between runs
+pub fn key(album: &Album) -> String {
+    match &album.title {
+        Some(title) => format!("{}\u{1f}{}\u{1f}{title}", album.artist, album.album),
+        None => format!("{}\u{1f}{}", album.artist, album.album),
+    }
+}
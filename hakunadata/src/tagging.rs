@@ -1,12 +1,230 @@
+use crate::config;
 use crate::context::AppContext;
-use crate::metadata::fetch::{process_query, FetchedMetadata};
+use crate::journal::{Journal, Snapshot};
+use crate::metadata::fetch::{
+    process_compilation_track, process_query, process_track_query, FetchedMetadata,
+};
+use crate::metadata::matching;
+use crate::path_pattern::{self, PathPattern};
+use crate::progress;
 use anyhow::{Context, Result};
-use lofty::config::WriteOptions;
+use clap::ValueEnum;
+use lofty::ape::{ApeFile, ApeItem, ApeTag};
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::{FileType, TaggedFile};
+use lofty::flac::FlacFile;
+use lofty::id3::v2::Id3v2Tag;
+use lofty::iff::aiff::AiffFile;
+use lofty::iff::wav::WavFile;
+use lofty::mp4::{Atom, AtomData, AtomIdent, Ilst, Mp4File};
+use lofty::mpeg::MpegFile;
+use lofty::ogg::{OpusFile, SpeexFile, VorbisComments, VorbisFile};
+use lofty::picture::PictureType;
 use lofty::prelude::*;
 use lofty::probe::Probe;
-use lofty::tag::{ItemKey, Tag};
+use lofty::tag::{ItemKey, ItemValue, Tag, TagItem, TagType};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
 use std::fs::File;
-use std::path::{Component, Path};
+use std::path::{Component, Path, PathBuf};
+
+/// Artist and album of a music file, from its tags or its path
+pub struct Track {
+    pub path: PathBuf,
+    pub artist: String,
+    pub album: String,
+    pub title: Option<String>,
+    /// Genre and label already in the file, as stored
+    pub genres: Vec<String>,
+    pub label: Option<String>,
+    pub album_artist: Option<String>,
+    /// Single, whose album-level genres don't fit
+    pub single: bool,
+    /// Track on a compilation, whose album is credited to various artists
+    pub compilation: bool,
+}
+
+impl Track {
+    /// Title to look the track up by instead of its album, if it has one
+    pub fn lookup_title(&self, by_track: bool) -> Option<&str> {
+        (by_track || self.single || self.compilation)
+            .then_some(self.title.as_deref())
+            .flatten()
+    }
+}
+
+/// Optional tags that can be written alongside genre and label
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagField {
+    Year,
+    OriginalDate,
+    Country,
+    Catno,
+    /// MusicBrainz release, release group and artist IDs
+    Musicbrainz,
+}
+
+/// Custom field that subgenres are written to with `--subgenres style`
+const STYLE_FIELD: &str = "STYLE";
+
+/// Custom fields hakunadata writes, which the generic tag can't see
+const CUSTOM_FIELDS: &[&str] = &[STYLE_FIELD];
+
+/// Freeform MP4 atom a custom field is stored in
+fn freeform_ident(name: &str) -> AtomIdent<'static> {
+    AtomIdent::Freeform {
+        mean: "com.apple.iTunes".into(),
+        name: name.to_string().into(),
+    }
+}
+
+/// Whether fetched genres replace or extend the genres already in the file
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenreMode {
+    Replace,
+    Append,
+}
+
+/// Where subgenres (Discogs styles) are written
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubgenreMode {
+    /// Not written at all
+    None,
+    /// Added to the genre tag after the genres
+    Genre,
+    /// Written to a separate STYLE field (TXXX:STYLE for ID3v2)
+    Style,
+}
+
+/// Which of a file's tags get written, for files carrying more than one
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagTargets {
+    /// Only the format's primary tag (Vorbis comments for FLAC, ID3v2 for MP3)
+    Primary,
+    /// Every tag in the file except ID3v1, which can't hold most fields
+    All,
+}
+
+impl TagTargets {
+    /// Tag types of the file to write, primary first
+    fn select(self, tagged_file: &TaggedFile) -> Vec<TagType> {
+        let primary = tagged_file.primary_tag_type();
+        match self {
+            Self::Primary => vec![primary],
+            Self::All => {
+                let mut types = vec![primary];
+                types.extend(tagged_file.tags().iter().map(Tag::tag_type).filter(|&t| {
+                    t != primary && t != TagType::Id3v1 && tagged_file.tag_support(t).is_writable()
+                }));
+                types
+            }
+        }
+    }
+}
+
+/// Controls which tags are written and how
+pub struct WritePolicy {
+    pub fields: Vec<TagField>,
+    pub genre_mode: GenreMode,
+    pub max_genres: Option<usize>,
+    pub multi_value: bool,
+    pub separator: String,
+    pub subgenres: SubgenreMode,
+    pub overwrite_label: bool,
+    pub tag_targets: TagTargets,
+}
+
+impl Default for WritePolicy {
+    fn default() -> Self {
+        Self {
+            fields: vec![
+                TagField::Year,
+                TagField::OriginalDate,
+                TagField::Country,
+                TagField::Catno,
+                TagField::Musicbrainz,
+            ],
+            genre_mode: GenreMode::Replace,
+            max_genres: None,
+            multi_value: false,
+            separator: String::from("/"),
+            subgenres: SubgenreMode::None,
+            overwrite_label: true,
+            tag_targets: TagTargets::Primary,
+        }
+    }
+}
+
+impl WritePolicy {
+    /// Defaults overridden by the `[write]` table of `config.toml`
+    pub fn load() -> Result<Self> {
+        let mut policy = Self::default();
+
+        let Some((path, doc)) = config::load("config.toml")? else {
+            return Ok(policy);
+        };
+        let Some(table) = doc.get("write").and_then(|item| item.as_table_like()) else {
+            return Ok(policy);
+        };
+
+        let context = || format!("Invalid [write] table in {}", path.display());
+
+        for (key, item) in table.iter() {
+            match key {
+                "fields" => {
+                    let values = item
+                        .as_array()
+                        .with_context(|| format!("'{key}' must be an array"))
+                        .with_context(context)?;
+                    policy.fields = values
+                        .iter()
+                        .map(|v| config::parse_enum(key, &toml_edit::Item::Value(v.clone())))
+                        .collect::<Result<_>>()
+                        .with_context(context)?;
+                }
+                "genre_mode" => {
+                    policy.genre_mode = config::parse_enum(key, item).with_context(context)?
+                }
+                "max_genres" => {
+                    let max = item
+                        .as_integer()
+                        .and_then(|max| usize::try_from(max).ok())
+                        .with_context(|| format!("'{key}' must be a positive integer"))
+                        .with_context(context)?;
+                    policy.max_genres = Some(max);
+                }
+                "multi_value" => {
+                    policy.multi_value = item
+                        .as_bool()
+                        .with_context(|| format!("'{key}' must be a boolean"))
+                        .with_context(context)?;
+                }
+                "separator" => {
+                    policy.separator = item
+                        .as_str()
+                        .with_context(|| format!("'{key}' must be a string"))
+                        .with_context(context)?
+                        .to_string();
+                }
+                "subgenres" => {
+                    policy.subgenres = config::parse_enum(key, item).with_context(context)?
+                }
+                "overwrite_label" => {
+                    policy.overwrite_label = item
+                        .as_bool()
+                        .with_context(|| format!("'{key}' must be a boolean"))
+                        .with_context(context)?;
+                }
+                "tag_targets" => {
+                    policy.tag_targets = config::parse_enum(key, item).with_context(context)?
+                }
+                _ => return Err(anyhow::anyhow!("Unknown key '{key}'").context(context())),
+            }
+        }
+
+        Ok(policy)
+    }
+}
 
 /// Process a music file to read or write metadata tags
 pub async fn process_file(ctx: &AppContext, path: &Path, read: bool, write: bool) -> Result<()> {
@@ -14,13 +232,53 @@ pub async fn process_file(ctx: &AppContext, path: &Path, read: bool, write: bool
         return Ok(());
     }
 
-    // Normalize the path to remove relative components like './' and '../'
-    let normalized_path = normalize_path(path);
-    let abs_path = std::env::current_dir().map_or_else(
-        |_| normalized_path.clone(),
-        |cwd| cwd.join(&normalized_path),
-    );
+    let Some(mut track) = read_track(path, ctx.path_pattern.as_ref())? else {
+        return Ok(());
+    };
+    if let Some(compilation) = ctx.compilation {
+        track.compilation = compilation;
+    }
 
+    let result = if let (true, Some(title)) = (track.compilation, track.title.as_deref()) {
+        println!(
+            "Processing compilation track: {} - {title} (on {})",
+            track.artist, track.album
+        );
+        process_compilation_track(ctx, &track.artist, &track.album, title).await?
+    } else if let Some(title) = track.lookup_title(ctx.by_track) {
+        println!("Processing track: {} - {title}", track.artist);
+        process_track_query(ctx, &track.artist, title).await?
+    } else {
+        println!("Processing: {} - {}", track.artist, track.album);
+        process_query(ctx, &track.artist, &track.album).await?
+    };
+
+    if !result.uncached.is_empty() {
+        println!(
+            "Not cached ({}), run again without --offline: {}",
+            result.uncached.join(", "),
+            track.path.display()
+        );
+        return Ok(());
+    }
+
+    if read {
+        print_proposed_tags(&result);
+    }
+
+    if write {
+        write_file(&track, &result, &ctx.policy, ctx.journal.as_ref())?;
+    }
+
+    Ok(())
+}
+
+/// Read the artist and album tags from a music file, skipping empty files
+///
+/// Files missing either tag fall back to `pattern`, or to guessing from the
+/// directory layout when no pattern is given.
+pub fn read_track(path: &Path, pattern: Option<&PathPattern>) -> Result<Option<Track>> {
+    let abs_path = resolve_path(path);
     let path_display = abs_path.display();
 
     // Check if file exists
@@ -35,107 +293,215 @@ pub async fn process_file(ctx: &AppContext, path: &Path, read: bool, write: bool
 
     if metadata.len() == 0 {
         eprintln!("Warning: File is empty, skipping: {path_display}");
-        return Ok(());
+        return Ok(None);
     }
 
-    // Read the file and extract current metadata
-    let (mut tagged_file, artist, album) = {
-        // Use the Probe API which can read from readers
-        let file = File::open(&abs_path)
-            .with_context(|| format!("Failed to open file: {path_display}"))?;
-        let mut probe = Probe::new(file);
-
-        // Hint the file type based on extension if possible
-        if let Some(file_type) = path
-            .extension()
-            .and_then(|e| e.to_str())
-            .and_then(lofty::file::FileType::from_ext)
-        {
-            probe = probe.set_file_type(file_type);
-        }
-
-        let mut tagged_file = probe
-            .read()
-            .with_context(|| format!("Failed to read tags from {path_display}"))?;
+    let tagged_file = open_tagged_file(&abs_path)?;
+    let tag = tagged_file.primary_tag();
+
+    let tag_artist = tag.and_then(|t| t.artist()).map(|a| a.to_string());
+    let tag_album = tag.and_then(|t| t.album()).map(|a| a.to_string());
+    let title = tag.and_then(|t| t.title()).map(|t| t.to_string());
+    let genres = tag
+        .map(|t| t.get_strings(ItemKey::Genre).map(str::to_string).collect())
+        .unwrap_or_default();
+    let label = tag.and_then(existing_label).map(str::to_string);
+    let album_artist = tag
+        .and_then(|t| t.get_string(ItemKey::AlbumArtist))
+        .map(str::to_string);
+
+    // Singles usually repeat the title as the album name
+    let compilation = tag.and_then(|t| t.get_string(ItemKey::FlagCompilation)) == Some("1")
+        || album_artist.as_deref().is_some_and(matching::is_various);
+    let single = tag_album
+        .as_deref()
+        .zip(title.as_deref())
+        .is_some_and(|(album, title)| album.eq_ignore_ascii_case(title));
+
+    let (artist, album) =
+        if let (Some(artist), Some(album)) = (tag_artist.clone(), tag_album.clone()) {
+            (artist, album)
+        } else {
+            let from_path = match pattern {
+                Some(pattern) => pattern.parse(&abs_path),
+                None => path_pattern::guess(&abs_path),
+            };
+            let (path_artist, path_album) = from_path.with_context(|| {
+                format!("Artist and album not found in tags or path: {path_display}")
+            })?;
+            (
+                tag_artist.unwrap_or(path_artist),
+                tag_album.unwrap_or(path_album),
+            )
+        };
+
+    Ok(Some(Track {
+        path: abs_path,
+        artist,
+        album,
+        title,
+        genres,
+        label,
+        album_artist,
+        single,
+        compilation,
+    }))
+}
 
-        let tag = tagged_file
-            .primary_tag_mut()
-            .context("No primary tag found")?;
+/// Write fetched metadata to a music file, restoring it from a backup on failure
+pub fn write_file(
+    track: &Track,
+    result: &FetchedMetadata,
+    policy: &WritePolicy,
+    journal: Option<&Journal>,
+) -> Result<()> {
+    let abs_path = resolve_path(&track.path);
+    let path_display = abs_path.display();
 
-        let artist = tag.artist().context("Artist not found")?.to_string();
-        let album = tag.album().context("Album not found")?.to_string();
+    let mut tagged_file = open_tagged_file(&abs_path)?;
+    let targets = policy.tag_targets.select(&tagged_file);
+    let snapshots: Vec<Snapshot> = match journal {
+        Some(_) => targets
+            .iter()
+            .map(|&tag_type| {
+                let custom_fields =
+                    read_custom_fields(&abs_path, tagged_file.file_type(), tag_type)?;
+                Ok(Snapshot::new(
+                    tagged_file.tag(tag_type),
+                    tag_type,
+                    custom_fields,
+                ))
+            })
+            .collect::<Result<_>>()?,
+        None => Vec::new(),
+    };
+
+    // Untagged files get a fresh tag of the format's preferred type
+    if tagged_file.primary_tag().is_none() && targets.contains(&tagged_file.primary_tag_type()) {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
 
-        (tagged_file, artist, album)
-    }; // file handle goes out of scope here
+    let mut custom_fields = Vec::new();
 
-    println!("Processing: {artist} - {album}");
+    for &tag_type in &targets {
+        // Get mutable reference to tag for writing
+        let tag = tagged_file
+            .tag_mut(tag_type)
+            .with_context(|| format!("No {tag_type:?} tag found"))?;
 
-    let result = process_query(ctx, &artist, &album).await?;
+        // Keep the artist and album that were inferred from the path
+        if tag.artist().is_none() {
+            tag.set_artist(track.artist.clone());
+        }
+        if tag.album().is_none() {
+            tag.set_album(track.album.clone());
+        }
 
-    if read {
-        print_proposed_tags(&result);
+        custom_fields = write_tags(tag, result, policy);
     }
 
-    if write {
-        // Get mutable reference to tag for writing
-        let tag = tagged_file
-            .primary_tag_mut()
-            .context("No primary tag found")?;
-        write_tags(tag, &result);
-
-        // Create a backup of the original file before modifying it to prevent data loss on failure
-        let backup_path = abs_path.with_extension(format!(
-            "backup.{}",
-            abs_path
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or("bak")
-        ));
-
-        // Copy the original file to backup
-        std::fs::copy(&abs_path, &backup_path)
-            .with_context(|| format!("Failed to create backup file: {}", backup_path.display()))?;
-
-        // Attempt to save the modified file to the original location
-        let save_result = tagged_file.save_to_path(&abs_path, WriteOptions::default());
-
-        match save_result {
-            Ok(()) => {
-                // Success: remove the backup file
-                let _ = std::fs::remove_file(&backup_path); // Ignore errors when removing backup
+    // Create a backup of the original file before modifying it to prevent data loss on failure
+    let backup_path = abs_path.with_extension(format!(
+        "backup.{}",
+        abs_path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("bak")
+    ));
+
+    // Copy the original file to backup
+    std::fs::copy(&abs_path, &backup_path)
+        .with_context(|| format!("Failed to create backup file: {}", backup_path.display()))?;
+
+    // Attempt to save the modified file to the original location
+    let save_result: Result<()> = if custom_fields.is_empty() {
+        tagged_file
+            .save_to_path(&abs_path, WriteOptions::default())
+            .map_err(Into::into)
+    } else {
+        targets.iter().try_for_each(|&tag_type| {
+            let tag = tagged_file
+                .tag(tag_type)
+                .with_context(|| format!("No {tag_type:?} tag found"))?;
+            save_with_custom_fields(tag, &custom_fields, &abs_path).map_err(Into::into)
+        })
+    };
+
+    match save_result {
+        Ok(()) => {
+            // Success: remove the backup file
+            let _ = std::fs::remove_file(&backup_path); // Ignore errors when removing backup
+
+            if let Some(journal) = journal {
+                for snapshot in snapshots {
+                    journal.record_tags(&abs_path, snapshot, result.artwork.is_some())?;
+                }
             }
-            Err(e) => {
-                // Failure: restore from backup before returning the error
-                if std::path::Path::exists(&backup_path) {
-                    if let Err(restore_err) = std::fs::copy(&backup_path, &abs_path) {
-                        eprintln!(
-                            "ERROR: Failed to restore from backup after write failure: {restore_err}",
-                        );
-                        eprintln!(
-                            "WARNING: Original file may be corrupted. Backup preserved at: {}",
-                            backup_path.display()
-                        );
-                        return Err(anyhow::anyhow!(
-                            "Also failed to restore from backup: {restore_err}"
-                        )
-                        .context(e));
-                    }
-                    eprintln!("Restored file from backup after write failure");
-                    let _ = std::fs::remove_file(&backup_path); // Clean up backup after successful restore
+        }
+        Err(e) => {
+            // Failure: restore from backup before returning the error
+            if std::path::Path::exists(&backup_path) {
+                if let Err(restore_err) = std::fs::copy(&backup_path, &abs_path) {
+                    progress::eprintln(format!(
+                        "ERROR: Failed to restore from backup after write failure: {restore_err}",
+                    ));
+                    progress::eprintln(format!(
+                        "WARNING: Original file may be corrupted. Backup preserved at: {}",
+                        backup_path.display()
+                    ));
+                    return Err(anyhow::anyhow!(
+                        "Also failed to restore from backup: {restore_err}"
+                    )
+                    .context(e));
                 }
-                return Err(anyhow::anyhow!("Failed to write tags to file").context(e));
+                progress::eprintln("Restored file from backup after write failure");
+                let _ = std::fs::remove_file(&backup_path); // Clean up backup after successful restore
             }
+            return Err(anyhow::anyhow!("Failed to write tags to file").context(e));
         }
-
-        println!("Tags written to {path_display}");
     }
 
+    progress::println(format!("Tags written to {path_display}"));
+
     Ok(())
 }
 
+/// Read a music file using the Probe API, hinting the file type from its extension
+pub fn open_tagged_file(path: &Path) -> Result<TaggedFile> {
+    let path_display = path.display();
+
+    // The file handle goes out of scope once the tags have been read
+    let file = File::open(path).with_context(|| format!("Failed to open file: {path_display}"))?;
+    let mut probe = Probe::new(file);
+
+    // Hint the file type based on extension if possible
+    if let Some(file_type) = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(lofty::file::FileType::from_ext)
+    {
+        probe = probe.set_file_type(file_type);
+    }
+
+    probe
+        .read()
+        .with_context(|| format!("Failed to read tags from {path_display}"))
+}
+
+/// Normalize a path and make it absolute relative to the current directory
+fn resolve_path(path: &Path) -> PathBuf {
+    // Normalize the path to remove relative components like './' and '../'
+    let normalized_path = normalize_path(path);
+    std::env::current_dir().map_or_else(
+        |_| normalized_path.clone(),
+        |cwd| cwd.join(&normalized_path),
+    )
+}
+
 /// Helper function to normalize a path by removing relative components
-fn normalize_path(path: &Path) -> std::path::PathBuf {
-    let mut result = std::path::PathBuf::new();
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
 
     for component in path.components() {
         match component {
@@ -153,24 +519,366 @@ fn normalize_path(path: &Path) -> std::path::PathBuf {
 }
 
 /// Print the proposed tags to stdout
-fn print_proposed_tags(metadata: &FetchedMetadata) {
-    println!("Proposed tags:");
+pub fn print_proposed_tags(metadata: &FetchedMetadata) {
+    print!("{}", format_proposed_tags(metadata));
+}
+
+/// Proposed tags as printed, so concurrently processed albums can print them in one go
+pub fn format_proposed_tags(metadata: &FetchedMetadata) -> String {
+    let mut out = String::from("Proposed tags:\n");
     let genre_str = metadata.genres.join("/");
-    println!("  Genre: {genre_str}");
+    let _ = writeln!(out, "  Genre: {genre_str}");
+
+    if !metadata.subgenres.is_empty() {
+        let _ = writeln!(out, "  Subgenres: {}", metadata.subgenres.join("/"));
+    }
 
     if let Some(label) = metadata.labels.first() {
-        println!("  Label: {label}");
+        let _ = writeln!(out, "  Label: {label}");
     }
+
+    if let Some(year) = &metadata.year {
+        let _ = writeln!(out, "  Year: {year}");
+    }
+
+    if let Some(date) = &metadata.original_date {
+        let _ = writeln!(out, "  Original date: {date}");
+    }
+
+    if let Some(country) = &metadata.country {
+        let _ = writeln!(out, "  Country: {country}");
+    }
+
+    if let Some(catno) = &metadata.catalog_number {
+        let _ = writeln!(out, "  Catalog number: {catno}");
+    }
+
+    if let Some(id) = &metadata.release_id {
+        let _ = writeln!(out, "  MusicBrainz release: {id}");
+    }
+
+    if let Some(id) = &metadata.recording_id {
+        let _ = writeln!(out, "  MusicBrainz recording: {id}");
+    }
+
+    if let Some(url) = &metadata.artwork_url {
+        let _ = writeln!(out, "  Artwork: {url}");
+    }
+
+    if !metadata.confidence.is_empty() {
+        let _ = writeln!(
+            out,
+            "  Confidence: {}",
+            format_confidence(&metadata.confidence)
+        );
+    }
+
+    out
 }
 
-/// Write metadata to the tag
-fn write_tags(tag: &mut Tag, metadata: &FetchedMetadata) {
-    let genre_str = metadata.genres.join("/");
-    tag.insert_text(ItemKey::Genre, genre_str);
+/// Format match scores as e.g. "discogs 0.97, musicbrainz 0.91"
+fn format_confidence(confidence: &[(&str, f64)]) -> String {
+    confidence
+        .iter()
+        .map(|(source, score)| format!("{source} {score:.2}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-    if let Some(label) = metadata.labels.first() {
+/// Write metadata to the tag, returning custom fields the generic tag can't hold
+fn write_tags(
+    tag: &mut Tag,
+    metadata: &FetchedMetadata,
+    policy: &WritePolicy,
+) -> Vec<(&'static str, Vec<String>)> {
+    let mut custom_fields = Vec::new();
+
+    let genres = genre_values(tag, metadata, policy);
+    if !genres.is_empty() {
+        insert_values(tag, ItemKey::Genre, genres, policy);
+    }
+
+    if policy.subgenres == SubgenreMode::Style && !metadata.subgenres.is_empty() {
+        let styles = if policy.multi_value {
+            metadata.subgenres.clone()
+        } else {
+            vec![metadata.subgenres.join(&policy.separator)]
+        };
+        custom_fields.push((STYLE_FIELD, styles));
+    }
+
+    if let Some(label) = metadata.labels.first()
+        && (policy.overwrite_label || existing_label(tag).is_none())
+    {
         tag.insert_text(ItemKey::Label, label.clone());
     }
+
+    let fields = [
+        (TagField::Year, ItemKey::RecordingDate, &metadata.year),
+        (
+            TagField::OriginalDate,
+            ItemKey::OriginalReleaseDate,
+            &metadata.original_date,
+        ),
+        (
+            TagField::Country,
+            ItemKey::ReleaseCountry,
+            &metadata.country,
+        ),
+        (
+            TagField::Catno,
+            ItemKey::CatalogNumber,
+            &metadata.catalog_number,
+        ),
+    ];
+
+    for (field, key, value) in fields {
+        if let Some(value) = value
+            && policy.fields.contains(&field)
+        {
+            tag.insert_text(key, value.clone());
+        }
+    }
+
+    // IDs let Picard and beets do exact lookups later on
+    if policy.fields.contains(&TagField::Musicbrainz) {
+        if let Some(id) = &metadata.release_id {
+            tag.insert_text(ItemKey::MusicBrainzReleaseId, id.clone());
+        }
+        if let Some(id) = &metadata.release_group_id {
+            tag.insert_text(ItemKey::MusicBrainzReleaseGroupId, id.clone());
+        }
+        if let Some(id) = &metadata.recording_id {
+            tag.insert_text(ItemKey::MusicBrainzRecordingId, id.clone());
+        }
+
+        // Recording credits are the track artists, which may not be the album's
+        let artist_keys: &[ItemKey] = if metadata.recording_id.is_some() {
+            &[ItemKey::MusicBrainzArtistId]
+        } else {
+            &[
+                ItemKey::MusicBrainzArtistId,
+                ItemKey::MusicBrainzReleaseArtistId,
+            ]
+        };
+        if !metadata.artist_ids.is_empty() {
+            for &key in artist_keys {
+                tag.remove_key(key);
+                for id in &metadata.artist_ids {
+                    tag.push(TagItem::new(key, ItemValue::Text(id.clone())));
+                }
+            }
+        }
+    }
+
+    if let Some(picture) = &metadata.artwork {
+        tag.remove_picture_type(PictureType::CoverFront);
+        tag.push_picture(picture.clone());
+    }
+
+    custom_fields
+}
+
+/// Label already in the tag
+fn existing_label(tag: &Tag) -> Option<&str> {
+    // ID3v2 stores the label in TPUB, which reads back as the publisher
+    tag.get_string(ItemKey::Label).or_else(|| {
+        (tag.tag_type() == TagType::Id3v2)
+            .then(|| tag.get_string(ItemKey::Publisher))
+            .flatten()
+    })
+}
+
+/// Genres to write according to the policy, without duplicates
+fn genre_values(tag: &Tag, metadata: &FetchedMetadata, policy: &WritePolicy) -> Vec<String> {
+    let mut candidates = Vec::new();
+
+    if policy.genre_mode == GenreMode::Append {
+        for existing in tag.get_strings(ItemKey::Genre) {
+            candidates.extend(
+                existing
+                    .split(policy.separator.as_str())
+                    .map(str::to_string),
+            );
+        }
+    }
+
+    candidates.extend(metadata.genres.iter().cloned());
+
+    if policy.subgenres == SubgenreMode::Genre {
+        candidates.extend(metadata.subgenres.iter().cloned());
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let mut genres: Vec<String> = candidates
+        .into_iter()
+        .map(|genre| genre.trim().to_string())
+        .filter(|genre| !genre.is_empty() && seen.insert(genre.to_lowercase()))
+        .collect();
+
+    if let Some(max) = policy.max_genres {
+        genres.truncate(max);
+    }
+
+    genres
+}
+
+/// Replace a key with either one joined value or one item per value
+fn insert_values(tag: &mut Tag, key: ItemKey, values: Vec<String>, policy: &WritePolicy) {
+    if policy.multi_value {
+        tag.remove_key(key);
+        for value in values {
+            tag.push(TagItem::new(key, ItemValue::Text(value)));
+        }
+    } else {
+        tag.insert_text(key, values.join(&policy.separator));
+    }
+}
+
+/// Values of the custom fields in one of a file's tags, read through its concrete format
+///
+/// Tag types that can't hold custom fields, or that lofty can't write, give no fields.
+fn read_custom_fields(
+    path: &Path,
+    file_type: FileType,
+    tag_type: TagType,
+) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let options = ParseOptions::new().read_properties(false);
+
+    let id3v2 = |tag: Option<&Id3v2Tag>| {
+        collect_custom_fields(|name| {
+            // ID3v2.4 separates multiple values with a null byte
+            tag.and_then(|tag| tag.get_user_text(name))
+                .map(|text| text.split('\0').map(str::to_string).collect())
+                .unwrap_or_default()
+        })
+    };
+    let vorbis = |tag: Option<&VorbisComments>| {
+        collect_custom_fields(|name| {
+            tag.map(|tag| tag.get_all(name).map(str::to_string).collect())
+                .unwrap_or_default()
+        })
+    };
+    let ape = |tag: Option<&ApeTag>| {
+        collect_custom_fields(
+            |name| match tag.and_then(|tag| tag.get(name)).map(ApeItem::value) {
+                Some(ItemValue::Text(text)) => text.split('\0').map(str::to_string).collect(),
+                _ => Vec::new(),
+            },
+        )
+    };
+
+    let fields = match (file_type, tag_type) {
+        (FileType::Mpeg, TagType::Id3v2) => id3v2(MpegFile::read_from(&mut file, options)?.id3v2()),
+        (FileType::Mpeg, TagType::Ape) => ape(MpegFile::read_from(&mut file, options)?.ape()),
+        (FileType::Aiff, TagType::Id3v2) => id3v2(AiffFile::read_from(&mut file, options)?.id3v2()),
+        (FileType::Wav, TagType::Id3v2) => id3v2(WavFile::read_from(&mut file, options)?.id3v2()),
+        (FileType::Ape, TagType::Ape) => ape(ApeFile::read_from(&mut file, options)?.ape()),
+        (FileType::Flac, TagType::VorbisComments) => {
+            vorbis(FlacFile::read_from(&mut file, options)?.vorbis_comments())
+        }
+        (FileType::Opus, TagType::VorbisComments) => vorbis(Some(
+            OpusFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        (FileType::Vorbis, TagType::VorbisComments) => vorbis(Some(
+            VorbisFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        (FileType::Speex, TagType::VorbisComments) => vorbis(Some(
+            SpeexFile::read_from(&mut file, options)?.vorbis_comments(),
+        )),
+        (FileType::Mp4, TagType::Mp4Ilst) => {
+            let mp4 = Mp4File::read_from(&mut file, options)?;
+            collect_custom_fields(|name| {
+                let atom = mp4.ilst().and_then(|ilst| ilst.get(&freeform_ident(name)));
+                atom.into_iter()
+                    .flat_map(Atom::data)
+                    .filter_map(|data| match data {
+                        AtomData::UTF8(text) => Some(text.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+        }
+        _ => BTreeMap::new(),
+    };
+
+    Ok(fields)
+}
+
+fn collect_custom_fields(values: impl Fn(&str) -> Vec<String>) -> BTreeMap<String, Vec<String>> {
+    CUSTOM_FIELDS
+        .iter()
+        .map(|name| ((*name).to_string(), values(name)))
+        .collect()
+}
+
+/// Save a tag through its concrete format so custom fields like STYLE can be added
+///
+/// Fields without values are removed.
+pub fn save_with_custom_fields(
+    tag: &Tag,
+    custom_fields: &[(&str, Vec<String>)],
+    path: &Path,
+) -> lofty::error::Result<()> {
+    let options = WriteOptions::default();
+
+    match tag.tag_type() {
+        TagType::Id3v2 => {
+            let mut id3v2 = Id3v2Tag::from(tag.clone());
+            for (name, values) in custom_fields {
+                if values.is_empty() {
+                    id3v2.remove_user_text(name);
+                } else {
+                    // ID3v2.4 separates multiple values with a null byte
+                    id3v2.insert_user_text((*name).to_string(), values.join("\0"));
+                }
+            }
+            id3v2.save_to_path(path, options)
+        }
+        TagType::VorbisComments => {
+            let mut comments = VorbisComments::from(tag.clone());
+            for (name, values) in custom_fields {
+                comments.remove(name).for_each(drop);
+                for value in values {
+                    comments.push((*name).to_string(), value.clone());
+                }
+            }
+            comments.save_to_path(path, options)
+        }
+        TagType::Mp4Ilst => {
+            let mut ilst = Ilst::from(tag.clone());
+            for (name, values) in custom_fields {
+                let ident = freeform_ident(name);
+                let data = values.iter().cloned().map(AtomData::UTF8).collect();
+                match Atom::from_collection(ident.clone(), data) {
+                    Some(atom) => ilst.insert(atom),
+                    None => ilst.remove(&ident).for_each(drop),
+                }
+            }
+            ilst.save_to_path(path, options)
+        }
+        TagType::Ape => {
+            let mut ape = ApeTag::from(tag.clone());
+            for (name, values) in custom_fields {
+                if values.is_empty() {
+                    ape.remove(name);
+                    continue;
+                }
+                let item = ApeItem::new((*name).to_string(), ItemValue::Text(values.join("\0")))?;
+                ape.insert(item);
+            }
+            ape.save_to_path(path, options)
+        }
+        _ => {
+            eprintln!(
+                "Warning: Custom fields aren't supported for {:?} tags, skipping them",
+                tag.tag_type()
+            );
+            tag.save_to_path(path, options)
+        }
+    }
 }
 
 /// Print metadata to stdout
@@ -201,4 +909,38 @@ pub fn print_metadata(result: &FetchedMetadata) {
             println!("  {label}");
         }
     }
+
+    let fields = [
+        ("Year", &result.year),
+        ("Original date", &result.original_date),
+        ("Country", &result.country),
+        ("Catalog number", &result.catalog_number),
+    ];
+
+    for (name, value) in fields {
+        println!("{name}: {}", value.as_deref().unwrap_or("(none)"));
+    }
+
+    if let Some(id) = &result.release_id {
+        println!("MusicBrainz release: {id}");
+    }
+    if let Some(id) = &result.release_group_id {
+        println!("MusicBrainz release group: {id}");
+    }
+    if let Some(id) = &result.recording_id {
+        println!("MusicBrainz recording: {id}");
+    }
+
+    if result.confidence.is_empty() {
+        println!("Confidence: (no match)");
+    } else {
+        println!("Confidence: {}", format_confidence(&result.confidence));
+    }
+
+    if !result.uncached.is_empty() {
+        println!(
+            "Not cached: {} (run again without --offline)",
+            result.uncached.join(", ")
+        );
+    }
 }
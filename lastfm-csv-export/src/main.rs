@@ -2,25 +2,36 @@ use clap::Parser;
 use csv::Writer;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
 const TRACKS_PER_PAGE: u32 = 200;
 const API_BASE_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+/// Last.fm asks for no more than five requests per second per API key
+const REQUEST_INTERVAL: Duration = Duration::from_millis(200);
 
 #[derive(Parser, Debug)]
 #[command(name = "lastfm-csv-export")]
 #[command(about = "Export Last.fm scrobbles to a .csv file", long_about = None)]
 struct Args {
-    /// Last.fm username
-    #[arg(short, long)]
-    username: String,
+    /// Last.fm username, can be given multiple times
+    #[arg(short, long, required_unless_present = "usernames_file")]
+    username: Vec<String>,
+
+    /// File with one Last.fm username per line
+    #[arg(long)]
+    usernames_file: Option<String>,
 
     /// Last.fm API key
     #[arg(short, long)]
     api_key: String,
 
-    /// Output CSV file path
+    /// Output CSV file path, with the username appended for multiple users
     #[arg(short, long, default_value = "scrobbles.csv")]
     output: String,
 
@@ -90,54 +101,203 @@ struct CsvRecord {
     date: String,
 }
 
+/// Spaces requests out across all exports, so running them concurrently
+/// doesn't go over the API's rate limit
+struct RateLimiter {
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new() -> Self {
+        Self {
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let slot = (*next).max(Instant::now());
+            *next = slot + REQUEST_INTERVAL;
+            slot
+        };
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// An export of one user's scrobbles
+struct Export<'a> {
+    client: &'a Client,
+    limiter: &'a RateLimiter,
+    args: &'a Args,
+    username: &'a str,
+    /// Put before progress lines when several users are exported at once
+    prefix: String,
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-
-    println!("Fetching scrobbles for user: {}", args.username);
+    let usernames = read_usernames(&args)?;
 
     let client = Client::new();
-    let tracks = fetch_all_tracks(&client, &args)?;
+    let limiter = RateLimiter::new();
+
+    if let [username] = usernames.as_slice() {
+        println!("Fetching scrobbles for user: {}", username);
+        let export = Export {
+            client: &client,
+            limiter: &limiter,
+            args: &args,
+            username,
+            prefix: String::new(),
+        };
+        export_user(&export, &args.output)?;
 
-    println!("Writing {} tracks to {}", tracks.len(), args.output);
-    write_csv(&args.output, &tracks)?;
+        println!("Done!");
+        return Ok(());
+    }
+
+    println!("Fetching scrobbles for {} users", usernames.len());
+
+    let results: Vec<(&String, Result<(), String>)> = thread::scope(|scope| {
+        let handles: Vec<_> = usernames
+            .iter()
+            .map(|username| {
+                let export = Export {
+                    client: &client,
+                    limiter: &limiter,
+                    args: &args,
+                    username,
+                    prefix: format!("[{}] ", username),
+                };
+                let output = user_output(&args.output, username);
+                scope.spawn(move || export_user(&export, &output).map_err(|e| e.to_string()))
+            })
+            .collect();
+
+        usernames
+            .iter()
+            .zip(handles)
+            .map(|(username, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|_| Err("export thread panicked".to_string()));
+                (username, result)
+            })
+            .collect()
+    });
+
+    let failed: Vec<_> = results
+        .iter()
+        .filter_map(|(username, result)| result.as_ref().err().map(|e| (username, e)))
+        .collect();
+    for (username, error) in &failed {
+        eprintln!("Export failed for {}: {}", username, error);
+    }
+    if !failed.is_empty() {
+        return Err(format!("{} of {} exports failed", failed.len(), results.len()).into());
+    }
 
     println!("Done!");
     Ok(())
 }
 
-fn fetch_all_tracks(client: &Client, args: &Args) -> Result<Vec<Track>, Box<dyn Error>> {
+/// Usernames from `--username` and `--usernames-file`, without duplicates
+fn read_usernames(args: &Args) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut usernames = args.username.clone();
+
+    if let Some(path) = &args.usernames_file {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read usernames file {}: {}", path, e))?;
+        usernames.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(ToString::to_string),
+        );
+    }
+
+    let mut seen = HashSet::new();
+    usernames.retain(|username| seen.insert(username.clone()));
+
+    if usernames.is_empty() {
+        return Err("No usernames given".into());
+    }
+
+    // Usernames end up in file names, so keep to what Last.fm allows
+    if let Some(invalid) = usernames.iter().find(|username| {
+        !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }) {
+        return Err(format!("Invalid Last.fm username: '{}'", invalid).into());
+    }
+    Ok(usernames)
+}
+
+/// `scrobbles.csv` becomes `scrobbles-<username>.csv`
+fn user_output(output: &str, username: &str) -> String {
+    let path = Path::new(output);
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("scrobbles");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}-{}.{}", stem, username, ext),
+        None => format!("{}-{}", stem, username),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+fn export_user(export: &Export, output: &str) -> Result<(), Box<dyn Error>> {
+    let tracks = fetch_all_tracks(export)?;
+
+    println!(
+        "{}Writing {} tracks to {}",
+        export.prefix,
+        tracks.len(),
+        output
+    );
+    write_csv(output, &tracks)
+}
+
+fn fetch_all_tracks(export: &Export) -> Result<Vec<Track>, Box<dyn Error>> {
     let mut all_tracks = Vec::new();
+    let prefix = &export.prefix;
 
     // First request to get total pages
-    let first_response = fetch_page(client, args, 1)?;
+    let first_response = fetch_page(export, 1)?;
     let total_pages: u32 = first_response.recenttracks.attr.total_pages.parse()?;
     let total_tracks: u32 = first_response.recenttracks.attr.total.parse()?;
 
-    println!("Total tracks: {}", total_tracks);
-    println!("Total pages: {}", total_pages);
+    println!("{}Total tracks: {}", prefix, total_tracks);
+    println!("{}Total pages: {}", prefix, total_pages);
 
     // Add tracks from first page
     all_tracks.extend(first_response.recenttracks.track);
 
     // Determine how many pages to fetch
-    let max_page = args
+    let max_page = export
+        .args
         .limit
         .map_or(total_pages, |limit| limit.min(total_pages));
 
     // Fetch remaining pages
     for page in 2..=max_page {
-        println!("Fetching page {}/{}", page, max_page);
-        let response = fetch_page(client, args, page)?;
+        println!("{}Fetching page {}/{}", prefix, page, max_page);
+        let response = fetch_page(export, page)?;
         all_tracks.extend(response.recenttracks.track);
     }
 
     Ok(all_tracks)
 }
 
-fn fetch_page(client: &Client, args: &Args, page: u32) -> Result<LastFmResponse, Box<dyn Error>> {
+fn fetch_page(export: &Export, page: u32) -> Result<LastFmResponse, Box<dyn Error>> {
+    let args = export.args;
     let mut url = format!(
         "{}?method=user.getrecenttracks&user={}&api_key={}&format=json&limit={}&page={}",
-        API_BASE_URL, args.username, args.api_key, TRACKS_PER_PAGE, page
+        API_BASE_URL, export.username, args.api_key, TRACKS_PER_PAGE, page
     );
 
     if let Some(from) = args.from {
@@ -148,7 +308,8 @@ fn fetch_page(client: &Client, args: &Args, page: u32) -> Result<LastFmResponse,
         url.push_str(&format!("&to={}", to));
     }
 
-    let response = client.get(&url).send()?;
+    export.limiter.wait();
+    let response = export.client.get(&url).send()?;
 
     if !response.status().is_success() {
         return Err(format!("API request failed with status: {}", response.status()).into());